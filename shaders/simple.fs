@@ -0,0 +1,33 @@
+#version 330 core
+
+struct Material {
+    vec4 ambient;
+    vec4 diffuse;
+    vec4 specular;
+    float shininess;
+};
+
+in vec3 FragPosition;
+in vec3 Normal;
+
+out vec4 color;
+
+uniform Material material;
+uniform vec3 lightPosition;
+uniform vec3 viewPosition;
+
+// Phong lighting from one white point light: a little ambient light everywhere, diffuse light on the
+// surfaces facing it and a highlight where it reflects towards the viewer
+void main()
+{
+    vec3 normal = normalize(Normal);
+    vec3 toLight = normalize(lightPosition - FragPosition);
+    vec3 toViewer = normalize(viewPosition - FragPosition);
+
+    vec3 ambient = 0.2 * material.ambient.rgb;
+    vec3 diffuse = max(dot(normal, toLight), 0.0) * material.diffuse.rgb;
+    float highlight = pow(max(dot(toViewer, reflect(-toLight, normal)), 0.0), max(material.shininess, 1.0));
+    vec3 specular = 0.5 * highlight * material.specular.rgb;
+
+    color = vec4(ambient * material.diffuse.rgb + diffuse + specular, material.diffuse.a);
+}
@@ -0,0 +1,19 @@
+#version 330 core
+layout (location = 0) in vec3 position;
+layout (location = 1) in vec3 normal;
+
+out vec3 FragPosition;
+out vec3 Normal;
+
+uniform mat4 model;
+uniform mat4 view;
+uniform mat4 projection;
+
+void main()
+{
+    vec4 world = model * vec4(position, 1.0);
+    FragPosition = world.xyz;
+    // scaling the model unevenly would skew its normals, the inverse transpose keeps them perpendicular
+    Normal = mat3(transpose(inverse(model))) * normal;
+    gl_Position = projection * view * world;
+}
@@ -0,0 +1,76 @@
+#version 330 core
+#define MAX_STOPS 8
+
+in vec2 TexCoords;
+in vec4 VertexColor;
+in vec3 Effect;
+in vec2 FillCoords;
+
+out vec4 color;
+
+uniform sampler2D text;
+uniform vec4 textColor;
+uniform bool distanceField;
+// the page holds RGBA color glyphs (emoji) instead of coverage or distance values
+uniform bool colorGlyphs;
+
+// 0 solid, 1 linear gradient from fillStart to fillEnd, 2 radial gradient around fillStart with
+// radius fillEnd.x, 3 fillTexture stretched over the text
+uniform int fillType;
+uniform vec2 fillStart;
+uniform vec2 fillEnd;
+uniform int stopCount;
+uniform float stopOffsets[MAX_STOPS];
+uniform vec4 stopColors[MAX_STOPS];
+uniform sampler2D fillTexture;
+
+vec4 gradient(float position)
+{
+    position = clamp(position, 0.0, 1.0);
+    vec4 result = stopColors[0];
+    for (int i = 1; i < stopCount; i++) {
+        float span = max(stopOffsets[i] - stopOffsets[i - 1], 0.0001);
+        result = mix(result, stopColors[i], clamp((position - stopOffsets[i - 1]) / span, 0.0, 1.0));
+    }
+    return result;
+}
+
+vec4 fillColor()
+{
+    if (fillType == 1) {
+        vec2 direction = fillEnd - fillStart;
+        return gradient(dot(FillCoords - fillStart, direction) / max(dot(direction, direction), 0.0001));
+    }
+    if (fillType == 2) {
+        return gradient(length(FillCoords - fillStart) / max(fillEnd.x, 0.0001));
+    }
+    if (fillType == 3) {
+        // images are stored from the top row down, the box is measured from the bottom up
+        return texture(fillTexture, vec2(FillCoords.x, 1.0 - FillCoords.y));
+    }
+    return vec4(1.0);
+}
+
+void main()
+{
+    vec4 texel = texture(text, TexCoords);
+    if (colorGlyphs) {
+        // color glyphs are not tinted, only faded; outlines, glows and shadows just take their shape
+        if (Effect.z > 0.5) {
+            color = vec4(texel.rgb, texel.a * textColor.a * VertexColor.a * fillColor().a);
+        } else {
+            color = textColor * VertexColor * fillColor() * vec4(1.0, 1.0, 1.0, texel.a);
+        }
+        return;
+    }
+
+    float value = texel.r;
+    float alpha = value;
+    if (distanceField) {
+        // the edge is at 0.5 for the glyph itself and lower for outlines, glows and shadows;
+        // smoothing over one screen pixel keeps it sharp at any scale, the softness blurs it
+        float width = fwidth(value) * 0.75 + Effect.y;
+        alpha = smoothstep(Effect.x - width, Effect.x + width, value);
+    }
+    color = textColor * VertexColor * fillColor() * vec4(1.0, 1.0, 1.0, alpha);
+}
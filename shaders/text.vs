@@ -0,0 +1,24 @@
+#version 330 core
+layout (location = 0) in vec4 vertex; // <vec2 position, vec2 texture coordinates>
+layout (location = 1) in vec4 color;
+layout (location = 2) in vec3 effect; // <distance field edge, softness, 1 for the glyphs themselves>
+layout (location = 3) in vec2 fill; // position inside the text's bounding box
+
+out vec2 TexCoords;
+out vec4 VertexColor;
+out vec3 Effect;
+out vec2 FillCoords;
+
+uniform mat4 projection;
+uniform mat3 transform;
+uniform vec2 offset;
+
+void main()
+{
+    vec3 position = transform * vec3(vertex.xy, 1.0);
+    gl_Position = projection * vec4(position.xy + offset, 0.0, 1.0);
+    TexCoords = vertex.zw;
+    VertexColor = color;
+    Effect = effect;
+    FillCoords = fill;
+}
@@ -0,0 +1,25 @@
+#version 330 core
+layout (location = 0) in vec4 vertex; // <vec2 position, vec2 texture coordinates>
+layout (location = 1) in vec4 color;
+layout (location = 2) in vec3 effect; // <distance field edge, softness, 1 for the glyphs themselves>
+layout (location = 3) in vec2 fill; // position inside the text's bounding box
+
+out vec2 TexCoords;
+out vec4 VertexColor;
+out vec3 Effect;
+out vec2 FillCoords;
+
+uniform mat4 model;
+uniform mat4 view;
+uniform mat4 projection;
+// shadow offsets are in the text's own pixels, so they turn with it
+uniform vec2 offset;
+
+void main()
+{
+    gl_Position = projection * view * model * vec4(vertex.xy + offset, 0.0, 1.0);
+    TexCoords = vertex.zw;
+    VertexColor = color;
+    Effect = effect;
+    FillCoords = fill;
+}
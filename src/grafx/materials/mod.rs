@@ -1,4 +1,5 @@
 pub mod shader;
+pub mod texture;
 
 use crate::grafx::Disposable;
 use crate::grafx::physics::Color;
@@ -21,7 +21,8 @@ impl Shader{
     }
 
     pub unsafe fn simple()->Self{ return Shader::new("./shaders/simple.vs", "./shaders/simple.fs"); }
-    pub unsafe fn text()->Self{ return Shader::new("/home/bsoft/Projects/text_example_rs/target/debug/shaders/text.vs", "/home/bsoft/Projects/text_example_rs/target/debug/shaders/text.fs"); }
+    pub unsafe fn text()->Self{ return Shader::new("./shaders/text.vs", "./shaders/text.fs"); }
+    pub unsafe fn text3d()->Self{ Shader::new("./shaders/text3d.vs", "./shaders/text.fs") }
     
     unsafe fn compile(shader_type: u32, shader_source:&str) -> u32{
         // Setup shader compilation checks
@@ -88,6 +89,13 @@ impl Shader{
         gl::ProgramUniform1i(self.shader_program, uniform, value);
     }
 
+    pub unsafe fn set_uniform_vector2(&self, name:&str, x:f32, y:f32){
+        let c_name = CString::new(name).unwrap();
+        let ptr = c_name.as_ptr();
+        let uniform = gl::GetUniformLocation(self.shader_program, ptr);
+        gl::ProgramUniform2f(self.shader_program, uniform, x, y);
+    }
+
     pub unsafe fn set_uniform_vector3(&self, name:&str, vector:&Vector3){
         let c_name = CString::new(name).unwrap();
         let ptr = c_name.as_ptr();
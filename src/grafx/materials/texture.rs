@@ -0,0 +1,40 @@
+use crate::grafx::Disposable;
+use std::ffi::c_void;
+
+// An RGBA image uploaded to the GPU
+pub struct Texture{ id:u32, width:i32, height:i32 }
+
+#[allow(dead_code)]
+impl Texture{
+    // The pixels are 4 bytes each (red, green, blue, alpha), rows from the top of the image down
+    pub unsafe fn from_rgba(width:i32, height:i32, pixels:&[u8])->Self{
+        assert_eq!(pixels.len(), (width * height * 4) as usize, "texture data does not match its size");
+        let mut id = 0;
+        gl::GenTextures(1, &mut id);
+        gl::BindTexture(gl::TEXTURE_2D, id);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+        gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA8 as i32, width, height, 0, gl::RGBA, gl::UNSIGNED_BYTE, pixels.as_ptr() as *const c_void);
+        gl::BindTexture(gl::TEXTURE_2D, 0);
+        Texture{ id, width, height }
+    }
+
+    pub fn get_id(&self)->u32{ self.id }
+    pub fn get_width(&self)->i32{ self.width }
+    pub fn get_height(&self)->i32{ self.height }
+
+    pub unsafe fn bind(&self, unit:u32){
+        gl::ActiveTexture(gl::TEXTURE0 + unit);
+        gl::BindTexture(gl::TEXTURE_2D, self.id);
+    }
+}
+
+impl Disposable for Texture{
+    fn dispose(&mut self) {
+        unsafe{ gl::DeleteTextures(1, &self.id); }
+        self.id = 0;
+    }
+}
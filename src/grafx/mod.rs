@@ -18,6 +18,7 @@ static mut LAST_TIME:f64 = 0.0;
 use glutin::event::{Event, WindowEvent};
 use glutin::event_loop::{ ControlFlow, EventLoop};
 use glutin::window::{WindowBuilder};
+use glutin::dpi::PhysicalPosition;
 use glutin::{ContextBuilder};
 
 #[allow(dead_code)]
@@ -42,7 +43,12 @@ impl WindowDetails{
 pub trait WindowHandler : Disposable{
     fn update(&mut self, delta: f32);
     fn resize(&mut self,width: i32, height:i32);
-    unsafe fn render(&self);
+    unsafe fn render(&mut self);
+    // Every window event the loop does not handle itself: keyboard, characters, mouse, focus...
+    fn event(&mut self, _event:&WindowEvent){}
+    // Where the input method should open its candidate window, in pixels from the bottom left corner. The
+    // input method is only enabled while this is Some, so keys are not composed when nothing takes text.
+    fn get_ime_position(&self)->Option<(f32, f32)>{ None }
 }
 
 pub fn init(detail:&WindowDetails)->(EventLoop<()>, WindowedContext<PossiblyCurrent>){
@@ -74,6 +80,7 @@ pub fn start(win_context: (EventLoop<()>, WindowedContext<PossiblyCurrent>), mut
         gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
     }
     let (event_loop, context)  = win_context;
+    let mut ime_allowed = false;
     event_loop.run(move | event, _, control_flow| {   
         match event {
             Event::LoopDestroyed => return,
@@ -87,7 +94,18 @@ pub fn start(win_context: (EventLoop<()>, WindowedContext<PossiblyCurrent>), mut
                     context.resize(size);
                     game.resize(size.width as i32, size.height as i32);
                 },
-                _ => (),
+                event => {
+                    game.event(&event);
+                    let position = game.get_ime_position();
+                    if position.is_some() != ime_allowed {
+                        ime_allowed = position.is_some();
+                        context.window().set_ime_allowed(ime_allowed);
+                    }
+                    if let Some((x, y)) = position {
+                        let height = context.window().inner_size().height as f32;
+                        context.window().set_ime_position(PhysicalPosition::new(x as f64, (height - y) as f64));
+                    }
+                },
             }
             _ =>()
         }
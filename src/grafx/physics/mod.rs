@@ -20,6 +20,48 @@ impl Viewport{
     pub fn get_height(&self)->f32{ self.height }
 }
 
+// A perspective view of the world from a position towards a target. Like Transformation3D the setters
+// only store the values, update() rebuilds the view and projection matrices from them.
+pub struct Camera{
+    view:Box<Matrix4>, projection:Box<Matrix4>, position:Box<Vector3>, target:Box<Vector3>, up:Box<Vector3>,
+    fov:f32, width:f32, height:f32, near:f32, far:f32
+}
+
+#[allow(dead_code)]
+impl Camera{
+    // Looks from 10 units in front of the origin at it, with a field of view in degrees
+    pub fn new(fov:f32, width:f32, height:f32)->Self{
+        let mut init = Camera{
+            view:Box::new(Matrix4::identity()), projection:Box::new(Matrix4::identity()), position:Box::new(Vector3::new(0.0, 0.0, 10.0)),
+            target:Box::new(Vector3::zero()), up:Box::new(Vector3::up()), fov, width, height, near:0.1, far:1000.0
+        };
+        init.update();
+        init
+    }
+
+    pub fn update(&mut self){
+        self.view = Box::new(Matrix4::LookAtMatrix(&self.position, &self.target, &self.up));
+        self.projection = Box::new(Matrix4::ProjectionMatrix(self.fov, self.width, self.height, self.near, self.far));
+    }
+
+    pub fn set_position(&mut self, x:f32, y:f32, z:f32){ self.position.set(x, y, z); }
+    pub fn look_at(&mut self, x:f32, y:f32, z:f32){ self.target.set(x, y, z); }
+    pub fn set_up(&mut self, x:f32, y:f32, z:f32){ self.up.set(x, y, z); }
+    pub fn set_fov(&mut self, fov:f32){ self.fov = fov; }
+    pub fn set_size(&mut self, width:f32, height:f32){ self.width = width; self.height = height; }
+    // Distances of the nearest and farthest points drawn
+    pub fn set_clipping(&mut self, near:f32, far:f32){ self.near = near; self.far = far; }
+
+    pub fn get_view(&self)->&[[f32; 4]; 4]{ self.view.getData() }
+    pub fn get_projection(&self)->&[[f32; 4]; 4]{ self.projection.getData() }
+    pub fn get_position(&self)->&Vector3{ &self.position }
+    pub fn get_target(&self)->&Vector3{ &self.target }
+    pub fn get_up(&self)->&Vector3{ &self.up }
+    pub fn get_fov(&self)->f32{ self.fov }
+    pub fn get_width(&self)->f32{ self.width }
+    pub fn get_height(&self)->f32{ self.height }
+}
+
 pub struct Rectangle{
     width:f32, height:f32, rotation:f32,
     lower_left:Box<Vector2>, upper_left:Box<Vector2>, 
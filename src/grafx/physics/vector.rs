@@ -1,6 +1,7 @@
 use std::ops::{Mul, Sub, Add};
 
 #[allow(non_snake_case)]
+#[derive(Clone, Copy)]
 pub struct Color{ pub red:f32, pub green:f32, pub blue:f32, pub alpha:f32}
 
 #[allow(dead_code)]
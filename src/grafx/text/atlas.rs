@@ -0,0 +1,445 @@
+use crate::grafx::Disposable;
+use crate::grafx::text::font::{ Font, FontManager };
+use crate::grafx::utils::{ Bearing, Character, Size };
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::c_void;
+use freetype::bitmap::PixelMode;
+use freetype::face::LoadFlag;
+use freetype::render_mode::RenderMode;
+use freetype::FtResult;
+use freetype::freetype_sys::{ FT_GlyphSlot, FT_GlyphSlotRec, FT_GlyphSlot_Embolden, FT_GlyphSlot_Oblique, FT_Vector, FT_GLYPH_FORMAT_OUTLINE };
+
+// Size in pixels of one (square) atlas page and the empty border kept around each glyph
+const PAGE_SIZE:i32 = 1024;
+const PADDING:i32 = 1;
+
+// Distance field glyphs are rasterized once at this pixel size and scaled to any font size when drawn.
+// The spread is how far in pixels the field reaches outside (and inside) the glyph outline.
+pub const DISTANCE_FIELD_SIZE:u32 = 48;
+pub const DISTANCE_FIELD_SPREAD:i32 = 6;
+
+thread_local!{
+    static GLYPH_ATLAS:RefCell<GlyphAtlas> = RefCell::new(GlyphAtlas::new());
+}
+
+// Bitmap glyphs are coverage masks rasterized for one font size. Distance field glyphs store the signed
+// distance to the outline instead, which stays sharp under any scale or rotation.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum TextRenderMode{ Bitmap, DistanceField }
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct GlyphKey{ font:Font, size:u32, glyph:u32, mode:TextRenderMode }
+
+// A single texture the glyph bitmaps are packed into, row by row (shelf packing). A page only holds
+// glyphs of one render mode, as the shader reads them differently, and either single channel coverage
+// (or distance) values or the RGBA pixels of color glyphs such as emoji. Pages are PAGE_SIZE square,
+// except the ones made for a single glyph too large for that.
+// The pixels are kept on the CPU; the rows glyphs were written to since the last upload are dirty, and
+// only those are uploaded again when the page is bound.
+struct AtlasPage{
+    texture:u32, mode:TextRenderMode, color:bool, width:i32, height:i32, pixels:Vec<u8>, cursor_x:i32, cursor_y:i32, row_height:i32, dirty:Option<(i32, i32)>
+}
+
+impl AtlasPage{
+    fn new(mode:TextRenderMode, color:bool, width:i32, height:i32)->Self{
+        let channels = if color { 4 } else { 1 };
+        AtlasPage{
+            texture:0, mode, color, width, height, pixels:vec![0; (width * height * channels) as usize],
+            cursor_x:PADDING, cursor_y:PADDING, row_height:0, dirty:None
+        }
+    }
+
+    fn is_shared(&self)->bool{ self.width == PAGE_SIZE && self.height == PAGE_SIZE }
+
+    fn get_channels(&self)->i32{ if self.color { 4 } else { 1 } }
+
+    fn allocate(&mut self, width:i32, height:i32)->Option<(i32, i32)>{
+        if self.cursor_x + width + PADDING > self.width {
+            self.cursor_x = PADDING;
+            self.cursor_y += self.row_height + PADDING;
+            self.row_height = 0;
+        }
+        if self.cursor_y + height + PADDING > self.height || width + 2 * PADDING > self.width {
+            return None;
+        }
+
+        let position = (self.cursor_x, self.cursor_y);
+        self.cursor_x += width + PADDING;
+        self.row_height = self.row_height.max(height);
+        Some(position)
+    }
+
+    // The pitch is the length of a row of the buffer in bytes, the width is in pixels
+    fn write(&mut self, x:i32, y:i32, width:i32, height:i32, pitch:i32, buffer:&[u8]){
+        let channels = self.get_channels();
+        let length = (width * channels) as usize;
+        for row in 0..height{
+            let source = (row * pitch) as usize;
+            let target = (((y + row) * self.width + x) * channels) as usize;
+            self.pixels[target..target + length].copy_from_slice(&buffer[source..source + length]);
+        }
+        self.dirty = Some(match self.dirty {
+            Some((top, bottom)) => (top.min(y), bottom.max(y + height)),
+            None => (y, y + height),
+        });
+    }
+
+    unsafe fn bind(&mut self){
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+        let format = if self.color { gl::RGBA } else { gl::RED };
+        if self.texture == 0 {
+            gl::GenTextures(1, &mut self.texture);
+            gl::BindTexture(gl::TEXTURE_2D, self.texture);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            // a new texture gets the whole page, glyphs written before included
+            let internal = if self.color { gl::RGBA8 } else { gl::R8 };
+            gl::TexImage2D(gl::TEXTURE_2D, 0, internal as i32, self.width, self.height, 0, format, gl::UNSIGNED_BYTE, self.pixels.as_ptr() as *const c_void);
+            self.dirty = None;
+            return;
+        }
+
+        gl::BindTexture(gl::TEXTURE_2D, self.texture);
+        if let Some((top, bottom)) = self.dirty.take() {
+            let offset = (top * self.width * self.get_channels()) as usize;
+            gl::TexSubImage2D(gl::TEXTURE_2D, 0, 0, top, self.width, bottom - top, format, gl::UNSIGNED_BYTE, self.pixels[offset..].as_ptr() as *const c_void);
+        }
+    }
+}
+
+// Cache of rasterized glyphs, shared by every Text of a thread. A glyph is rendered with FreeType
+// the first time its (font, pixel size, glyph id) is requested; afterwards it is only looked up.
+pub struct GlyphAtlas{ glyphs:HashMap<GlyphKey, Character>, solids:HashMap<TextRenderMode, Character>, pages:Vec<AtlasPage> }
+
+#[allow(dead_code)]
+impl GlyphAtlas{
+    fn new()->Self{
+        GlyphAtlas{ glyphs:HashMap::new(), solids:HashMap::new(), pages:Vec::new() }
+    }
+
+    // Runs f with the atlas of this thread, whose pages belong to the GL context current on it. f must
+    // not reach the atlas again, which would panic; it may use the FontManager.
+    pub fn with<R>(f:impl FnOnce(&mut GlyphAtlas)->R)->R{
+        GLYPH_ATLAS.with(|atlas| f(&mut atlas.borrow_mut()))
+    }
+
+    // Glyph index used to draw a character: its own glyph, else U+FFFD, else the font's notdef glyph (0)
+    pub fn get_glyph_index(font:Font, ch:char)->u32{
+        FontManager::with(|manager| {
+            let face = manager.get_face(font);
+            match face.get_char_index(ch as usize) {
+                0 => face.get_char_index(char::REPLACEMENT_CHARACTER as usize),
+                glyph => glyph,
+            }
+        })
+    }
+
+    // Distance field glyphs are always rasterized at DISTANCE_FIELD_SIZE, whatever size is asked for
+    pub fn get_glyph(&mut self, font:Font, size:u32, glyph:u32, mode:TextRenderMode)->Character{
+        let size = if mode == TextRenderMode::DistanceField { DISTANCE_FIELD_SIZE } else { size };
+        let key = GlyphKey{ font, size, glyph, mode };
+        if let Some(character) = self.glyphs.get(&key) {
+            return character.clone();
+        }
+
+        let character = self.rasterize(font, size, glyph, mode);
+        self.glyphs.insert(key, character.clone());
+        character
+    }
+
+    // A small opaque block that underlines and strikethroughs are drawn with. Its uv only covers the
+    // middle of the block, so filtering never blends in the empty pixels around it.
+    pub fn get_solid(&mut self, mode:TextRenderMode)->Character{
+        if let Some(character) = self.solids.get(&mode) {
+            return character.clone();
+        }
+
+        let (page, x, y) = self.allocate(4, 4, mode, false);
+        self.pages[page].write(x, y, 4, 4, 4, &[255; 16]);
+        let size = PAGE_SIZE as f32;
+        let uv = [(x + 1) as f32 / size, (y + 1) as f32 / size, (x + 3) as f32 / size, (y + 3) as f32 / size];
+        let character = Character::new(page, uv, Size::new(2, 2), Bearing(0, 0), FT_Vector::default());
+        self.solids.insert(mode, character.clone());
+        character
+    }
+
+    // Loads and renders a glyph into the face's slot. The outlines of synthesized styles are emboldened
+    // and slanted first; bitmap glyphs are left as they are.
+    fn load(manager:&FontManager, font:Font, glyph:u32)->FtResult<()>{
+        let face = manager.get_face(font);
+        let (embolden, oblique) = manager.get_synthesis(font);
+        if !embolden && !oblique {
+            return face.load_glyph(glyph, LoadFlag::RENDER | LoadFlag::COLOR);
+        }
+
+        GlyphAtlas::load_outline(manager, font, glyph, LoadFlag::COLOR)?;
+        face.glyph().render_glyph(RenderMode::Normal)
+    }
+
+    // Loads a glyph into its face's slot without rendering it, emboldened and slanted when the face
+    // stands in for a missing bold or italic variant
+    pub(crate) fn load_outline(manager:&FontManager, font:Font, glyph:u32, flags:LoadFlag)->FtResult<()>{
+        let (embolden, oblique) = manager.get_synthesis(font);
+        let face = manager.get_face(font);
+        face.load_glyph(glyph, flags)?;
+        let slot = face.glyph();
+        if slot.raw().format == FT_GLYPH_FORMAT_OUTLINE {
+            let raw = slot.raw() as *const FT_GlyphSlotRec as FT_GlyphSlot;
+            unsafe{
+                if embolden { FT_GlyphSlot_Embolden(raw); }
+                if oblique { FT_GlyphSlot_Oblique(raw); }
+            }
+        }
+        Ok(())
+    }
+
+    fn rasterize(&mut self, font:Font, size:u32, glyph:u32, mode:TextRenderMode)->Character{
+        let character = FontManager::with(|manager| {
+            let scale = match manager.set_size(font, size) {
+                Some(scale) => scale,
+                None => return Some(Character::empty()),
+            };
+            GlyphAtlas::load(manager, font, glyph).ok()?;
+            Some(self.place_slot(manager, font, scale, mode))
+        });
+        // a broken glyph is shown as notdef, and if even that fails as nothing
+        character.unwrap_or_else(|| if glyph != 0 { self.rasterize(font, size, 0, mode) } else { Character::empty() })
+    }
+
+    // Copies the glyph just rendered into its face's slot to a page
+    fn place_slot(&mut self, manager:&FontManager, font:Font, scale:f32, mode:TextRenderMode)->Character{
+        let slot = manager.get_face(font).glyph();
+        let bitmap = slot.bitmap();
+        let (mut width, mut height, mut pitch) = (bitmap.width(), bitmap.rows(), bitmap.pitch());
+        let (mut left, mut top, mut advance) = (slot.bitmap_left(), slot.bitmap_top(), slot.advance());
+
+        // empty glyphs (spaces) have no buffer at all
+        if width == 0 || height == 0 {
+            advance.x = (advance.x as f32 * scale) as _;
+            return Character::new(0, [0.0; 4], Size::new(0, 0), Bearing((left as f32 * scale) as i32, (top as f32 * scale) as i32), advance);
+        }
+        let mut buffer = bitmap.buffer().to_vec();
+
+        if let Ok(PixelMode::Bgra) = bitmap.pixel_mode() {
+            return self.rasterize_color(&buffer, width, height, pitch, (left, top), advance, scale, mode);
+        }
+
+        if mode == TextRenderMode::DistanceField {
+            let spread = DISTANCE_FIELD_SPREAD;
+            buffer = GlyphAtlas::distance_field(&buffer, width, height, pitch, spread);
+            width += 2 * spread;
+            height += 2 * spread;
+            pitch = width;
+            left -= spread;
+            top += spread;
+        }
+
+        self.place(&buffer, width, height, pitch, (left, top), advance, mode, false)
+    }
+
+    // Color glyphs come as premultiplied BGRA in the size of their strike. They are scaled to the size
+    // asked for and stored as straight RGBA, which the shader draws without tinting. Distance field
+    // texts get them with the same empty border as their other glyphs, so every glyph is measured alike.
+    #[allow(clippy::too_many_arguments)]
+    fn rasterize_color(&mut self, buffer:&[u8], width:i32, height:i32, pitch:i32, bearing:(i32, i32), mut advance:FT_Vector, scale:f32, mode:TextRenderMode)->Character{
+        let (scaled_width, scaled_height) = (((width as f32 * scale).round() as i32).max(1), ((height as f32 * scale).round() as i32).max(1));
+        let border = if mode == TextRenderMode::DistanceField { DISTANCE_FIELD_SPREAD } else { 0 };
+        let (field_width, field_height) = (scaled_width + 2 * border, scaled_height + 2 * border);
+
+        let mut pixels = vec![0u8; (field_width * field_height * 4) as usize];
+        for y in 0..scaled_height{
+            for x in 0..scaled_width{
+                // the average of the strike pixels this pixel covers, at least the one under its center
+                let (from_x, from_y) = (((x as f32 / scale) as i32).min(width - 1), ((y as f32 / scale) as i32).min(height - 1));
+                let to_x = (((x + 1) as f32 / scale) as i32).clamp(from_x + 1, width);
+                let to_y = (((y + 1) as f32 / scale) as i32).clamp(from_y + 1, height);
+                let mut sum = [0u32; 4];
+                for source_y in from_y..to_y{
+                    for source_x in from_x..to_x{
+                        let source = (source_y * pitch + source_x * 4) as usize;
+                        for channel in 0..4{
+                            sum[channel] += buffer[source + channel] as u32;
+                        }
+                    }
+                }
+                let count = ((to_x - from_x) * (to_y - from_y)) as u32;
+                let [blue, green, red, alpha] = sum.map(|value| value / count);
+                let unmultiply = |value:u32| (value * 255).checked_div(alpha).map_or(0, |value| value.min(255) as u8);
+                let target = (((y + border) * field_width + x + border) * 4) as usize;
+                pixels[target..target + 4].copy_from_slice(&[unmultiply(red), unmultiply(green), unmultiply(blue), alpha as u8]);
+            }
+        }
+
+        let left = (bearing.0 as f32 * scale).round() as i32 - border;
+        let top = (bearing.1 as f32 * scale).round() as i32 + border;
+        advance.x = (advance.x as f32 * scale) as _;
+        self.place(&pixels, field_width, field_height, field_width * 4, (left, top), advance, mode, true)
+    }
+
+    // Copies a rasterized glyph into a page with room for it
+    #[allow(clippy::too_many_arguments)]
+    fn place(&mut self, buffer:&[u8], width:i32, height:i32, pitch:i32, bearing:(i32, i32), advance:FT_Vector, mode:TextRenderMode, color:bool)->Character{
+        let (page, x, y) = self.allocate(width, height, mode, color);
+        self.pages[page].write(x, y, width, height, pitch, buffer);
+
+        let (page_width, page_height) = (self.pages[page].width as f32, self.pages[page].height as f32);
+        let uv = [x as f32 / page_width, y as f32 / page_height, (x + width) as f32 / page_width, (y + height) as f32 / page_height];
+        Character::new(page, uv, Size::new(width, height), Bearing(bearing.0, bearing.1), advance)
+    }
+
+    // Signed distance field of a coverage bitmap, grown by `spread` pixels on every side, mapped so the
+    // outline is at 0.5 and values above it are inside the glyph. The outline is not taken between pixel
+    // centres: in every pixel it crosses, its place follows from the coverage of the pixel and the direction
+    // of the coverage gradient, so edges stay straight however far the glyph is scaled up.
+    fn distance_field(buffer:&[u8], width:i32, height:i32, pitch:i32, spread:i32)->Vec<u8>{
+        let coverage = |x:i32, y:i32| if x >= 0 && y >= 0 && x < width && y < height { buffer[(y * pitch + x) as usize] as f32 / 255.0 } else { 0.0 };
+
+        // the piece of outline in each pixel it crosses, as a point on it and its normal pointing inside
+        let mut edges = vec![None; (width * height) as usize];
+        for y in 0..height{
+            for x in 0..width{
+                let alpha = coverage(x, y);
+                let step = [(1, 0), (-1, 0), (0, 1), (0, -1)].iter().any(|&(dx, dy)| (coverage(x + dx, y + dy) - alpha).abs() >= 1.0);
+                if (alpha <= 0.0 || alpha >= 1.0) && !step {
+                    continue;
+                }
+                let gradient_x = coverage(x + 1, y - 1) + 2.0 * coverage(x + 1, y) + coverage(x + 1, y + 1) - coverage(x - 1, y - 1) - 2.0 * coverage(x - 1, y) - coverage(x - 1, y + 1);
+                let gradient_y = coverage(x - 1, y + 1) + 2.0 * coverage(x, y + 1) + coverage(x + 1, y + 1) - coverage(x - 1, y - 1) - 2.0 * coverage(x, y - 1) - coverage(x + 1, y - 1);
+                let length = gradient_x.hypot(gradient_y);
+                let (normal_x, normal_y) = if length > 0.0 { (gradient_x / length, gradient_y / length) } else { (0.0, 0.0) };
+                let distance = GlyphAtlas::edge_distance(normal_x, normal_y, alpha);
+                edges[(y * width + x) as usize] = Some((x as f32 + normal_x * distance, y as f32 + normal_y * distance, normal_x, normal_y));
+            }
+        }
+
+        let mut field = Vec::with_capacity(((width + 2 * spread) * (height + 2 * spread)) as usize);
+        for y in -spread..height + spread{
+            for x in -spread..width + spread{
+                let mut nearest = spread as f32;
+                for edge_y in (y - spread).max(0)..(y + spread + 1).min(height){
+                    for edge_x in (x - spread).max(0)..(x + spread + 1).min(width){
+                        let Some((point_x, point_y, normal_x, normal_y)) = edges[(edge_y * width + edge_x) as usize] else { continue };
+                        let (dx, dy) = (x as f32 - point_x, y as f32 - point_y);
+                        // the piece of outline reaches half a pixel to both sides of its point
+                        let distance = if normal_x == 0.0 && normal_y == 0.0 {
+                            dx.hypot(dy)
+                        }else{
+                            let (across, along) = (dx * normal_x + dy * normal_y, dy * normal_x - dx * normal_y);
+                            across.hypot((along.abs() - 0.5).max(0.0))
+                        };
+                        nearest = nearest.min(distance);
+                    }
+                }
+                let distance = if coverage(x, y) > 0.5 { nearest } else { -nearest };
+                field.push(((0.5 + distance / (2.0 * spread as f32)).clamp(0.0, 1.0) * 255.0).round() as u8);
+            }
+        }
+        field
+    }
+
+    // How far the outline lies from the centre of a pixel with this coverage, along the normal (positive
+    // when the centre is outside). A straight outline cuts the pixel in a triangle near its corners and in
+    // a trapezoid between them, so the coverage grows with the distance like their areas do.
+    fn edge_distance(normal_x:f32, normal_y:f32, alpha:f32)->f32{
+        if normal_x == 0.0 || normal_y == 0.0 {
+            return 0.5 - alpha;
+        }
+        let (x, y) = (normal_x.abs().max(normal_y.abs()), normal_x.abs().min(normal_y.abs()));
+        let corner = 0.5 * y / x;
+        if alpha < corner {
+            0.5 * (x + y) - (2.0 * x * y * alpha).sqrt()
+        }else if alpha < 1.0 - corner {
+            (0.5 - alpha) * x
+        }else{
+            (2.0 * x * y * (1.0 - alpha)).sqrt() - 0.5 * (x + y)
+        }
+    }
+
+    fn allocate(&mut self, width:i32, height:i32, mode:TextRenderMode, color:bool)->(usize, i32, i32){
+        if let Some(index) = self.pages.iter().rposition(|page| page.mode == mode && page.color == color && page.is_shared()) {
+            if let Some((x, y)) = self.pages[index].allocate(width, height) {
+                return (index, x, y);
+            }
+        }
+
+        // a glyph larger than a page, from a very large font size, gets a page just big enough for it
+        let mut page = AtlasPage::new(mode, color, PAGE_SIZE.max(width + 2 * PADDING), PAGE_SIZE.max(height + 2 * PADDING));
+        let (x, y) = page.allocate(width, height).unwrap_or((PADDING, PADDING));
+        self.pages.push(page);
+        (self.pages.len() - 1, x, y)
+    }
+
+    pub fn get_page_count(&self)->usize{ self.pages.len() }
+
+    pub fn get_page_mode(&self, page:usize)->TextRenderMode{
+        self.pages.get(page).map_or(TextRenderMode::Bitmap, |page| page.mode)
+    }
+
+    // Whether a page holds color glyphs, which are drawn in their own colors
+    pub fn is_color_page(&self, page:usize)->bool{
+        self.pages.get(page).is_some_and(|page| page.color)
+    }
+
+    // Binds the texture of the given page, uploading the rows glyphs were added to since the last upload
+    pub unsafe fn bind_page(&mut self, page:usize){
+        if let Some(page) = self.pages.get_mut(page) {
+            page.bind();
+        }
+    }
+}
+
+impl Disposable for GlyphAtlas{
+    fn dispose(&mut self) {
+        for page in &mut self.pages{
+            if page.texture != 0 {
+                unsafe{ gl::DeleteTextures(1, &page.texture); }
+                page.texture = 0;
+                page.dirty = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn scaled_up_diagonal_edges_have_no_steps(){
+        // an antialiased half plane left of x = 0.37 * y + 11.2
+        let (size, spread) = (32, DISTANCE_FIELD_SPREAD);
+        let edge = |y:f32| 0.37 * y + 11.2;
+        let mut buffer = vec![0u8; (size * size) as usize];
+        for y in 0..size{
+            for x in 0..size{
+                let mut inside = 0;
+                for sample in 0..256{
+                    let (sample_x, sample_y) = (x as f32 + (sample % 16) as f32 / 16.0 + 1.0 / 32.0, y as f32 + (sample / 16) as f32 / 16.0 + 1.0 / 32.0);
+                    inside += (sample_x < edge(sample_y)) as u32;
+                }
+                buffer[(y * size + x) as usize] = (inside as f32 / 256.0 * 255.0).round() as u8;
+            }
+        }
+        let field = GlyphAtlas::distance_field(&buffer, size, size, size, spread);
+        let field_size = size + 2 * spread;
+        let sample = |x:f32, y:f32| {
+            let (left, top) = (x.floor() as i32, y.floor() as i32);
+            let (fx, fy) = (x - left as f32, y - top as f32);
+            let at = |x:i32, y:i32| field[(y * field_size + x) as usize] as f32 / 255.0;
+            (at(left, top) * (1.0 - fx) + at(left + 1, top) * fx) * (1.0 - fy) + (at(left, top + 1) * (1.0 - fx) + at(left + 1, top + 1) * fx) * fy
+        };
+
+        // scaled up eight times, the outline drawn at 0.5 must follow the straight edge on every row
+        for row in 8 * (spread + 4)..8 * (spread + size - 4){
+            let y = row as f32 / 8.0;
+            let column = (spread..spread + size).find(|&x| sample(x as f32 + 1.0, y) < 0.5).unwrap() as f32;
+            let (before, after) = (sample(column, y), sample(column + 1.0, y));
+            let crossing = column + (before - 0.5) / (before - after);
+            let expected = edge(y - spread as f32 + 0.5) + spread as f32 - 0.5;
+            assert!((crossing - expected).abs() <= 0.05, "row {}: edge at {} instead of {}", y, crossing, expected);
+        }
+    }
+}
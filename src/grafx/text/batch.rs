@@ -0,0 +1,175 @@
+use crate::grafx::Disposable;
+use crate::grafx::physics::{ Color, Viewport };
+use crate::grafx::physics::{ Matrix, Matrix3 };
+use crate::grafx::text::{ with_text_shader, Text, TextFill, QUAD_VERTEX_SIZE };
+use crate::grafx::text::style::StyleLayer;
+use crate::grafx::text::atlas::{ GlyphAtlas, TextRenderMode };
+use gl::types::{ GLfloat, GLsizei };
+use std::ffi::c_void;
+use std::mem;
+
+// x, y, u, v, red, green, blue, alpha, edge, softness, glyph layer, fill x, fill y
+const VERTEX_SIZE:usize = 13;
+
+// vertices and indices of the quads on each atlas page
+type PageBuffers = Vec<(Vec<f32>, Vec<u32>)>;
+
+// Collects the glyph quads of many Text objects during a frame and draws them together. Each text's
+// transformation, color and style layers are baked into its vertices, so the whole batch needs one draw
+// call per atlas page instead of one per Text. Gradient and texture fills need their own uniforms, so
+// texts using them are drawn separately after the rest.
+pub struct TextBatch{
+    voa:u32, vbo:u32, ebo:u32, pages:PageBuffers, fills:Vec<(TextFill, PageBuffers)>, draw_calls:usize
+}
+
+#[allow(dead_code)]
+impl TextBatch{
+    pub fn new()->Self{
+        let (voa, vbo, ebo) = unsafe{
+            let mut voa = 0;
+            let mut vbo = 0;
+            let mut ebo = 0;
+            gl::GenVertexArrays(1, &mut voa);
+            gl::GenBuffers(1, &mut vbo);
+            gl::GenBuffers(1, &mut ebo);
+            gl::BindVertexArray(voa);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
+
+            let stride = (VERTEX_SIZE * mem::size_of::<GLfloat>()) as GLsizei;
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(0, 4, gl::FLOAT, gl::FALSE, stride, std::ptr::null());
+            gl::EnableVertexAttribArray(1);
+            gl::VertexAttribPointer(1, 4, gl::FLOAT, gl::FALSE, stride, (4 * mem::size_of::<GLfloat>()) as *const c_void);
+            gl::EnableVertexAttribArray(2);
+            gl::VertexAttribPointer(2, 3, gl::FLOAT, gl::FALSE, stride, (8 * mem::size_of::<GLfloat>()) as *const c_void);
+            gl::EnableVertexAttribArray(3);
+            gl::VertexAttribPointer(3, 2, gl::FLOAT, gl::FALSE, stride, (11 * mem::size_of::<GLfloat>()) as *const c_void);
+
+            gl::BindVertexArray(0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+            (voa, vbo, ebo)
+        };
+        TextBatch{ voa, vbo, ebo, pages:Vec::new(), fills:Vec::new(), draw_calls:0 }
+    }
+
+    pub fn begin(&mut self){
+        for (vertices, indices) in &mut self.pages{
+            vertices.clear();
+            indices.clear();
+        }
+        self.fills.clear();
+    }
+
+    pub fn add(&mut self, text:&Text){
+        for layer in text.get_layers(){
+            if layer.filled && !text.get_fill().is_solid() {
+                let mut pages = Vec::new();
+                TextBatch::add_layer(&mut pages, text, &layer);
+                self.fills.push((text.get_fill().clone(), pages));
+            }else{
+                TextBatch::add_layer(&mut self.pages, text, &layer);
+            }
+        }
+    }
+
+    fn add_layer(pages:&mut PageBuffers, text:&Text, layer:&StyleLayer){
+        let matrix = text.transform.getTransformMatrix();
+        let vertices = text.get_vertices();
+        let (effect, (offset_x, offset_y)) = (&layer.color, layer.offset);
+
+        for range in text.get_ranges(){
+            if pages.len() <= range.page {
+                pages.resize_with(range.page + 1, || (Vec::new(), Vec::new()));
+            }
+            let (page_vertices, page_indices) = &mut pages[range.page];
+
+            // every quad of a Text takes 4 vertices and 6 indices
+            for quad in range.offset / 6..(range.offset + range.count) / 6{
+                let base = (page_vertices.len() / VERTEX_SIZE) as u32;
+                for vertex in vertices[quad * 4 * QUAD_VERTEX_SIZE..(quad + 1) * 4 * QUAD_VERTEX_SIZE].chunks(QUAD_VERTEX_SIZE){
+                    let (x, y) = (vertex[0], vertex[1]);
+                    // the glyphs keep the colors of their spans, the effects are drawn in one color
+                    let color = if layer.filled { [vertex[6], vertex[7], vertex[8], vertex[9]] } else { [effect.red, effect.green, effect.blue, effect.alpha] };
+                    page_vertices.extend_from_slice(&[
+                        matrix[0][0] * x + matrix[0][1] * y + matrix[0][2] + offset_x,
+                        matrix[1][0] * x + matrix[1][1] * y + matrix[1][2] + offset_y,
+                        vertex[2], vertex[3],
+                        color[0], color[1], color[2], color[3],
+                        layer.edge, layer.softness, layer.filled as i32 as f32,
+                        vertex[4], vertex[5]
+                    ]);
+                }
+                page_indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+            }
+        }
+    }
+
+    // Uploads everything added since begin() and draws it, one call per atlas page in use
+    pub fn end(&mut self, port:&Viewport){
+        let mut vertices:Vec<f32> = Vec::new();
+        let mut indices:Vec<u32> = Vec::new();
+        let mut ranges = Vec::new();
+        // the solid texts first, then every text with its own fill
+        let groups = Some((&TextFill::Solid, &self.pages)).into_iter().chain(self.fills.iter().map(|(fill, pages)| (fill, pages)));
+        for (fill, pages) in groups{
+            for (page, (page_vertices, page_indices)) in pages.iter().enumerate(){
+                if page_indices.is_empty() { continue; }
+                let base = (vertices.len() / VERTEX_SIZE) as u32;
+                ranges.push((fill, page, indices.len(), page_indices.len()));
+                vertices.extend_from_slice(page_vertices);
+                indices.extend(page_indices.iter().map(|index| index + base));
+            }
+        }
+
+        self.draw_calls = ranges.len();
+        if ranges.is_empty() { return; }
+
+        unsafe{
+            gl::BindVertexArray(self.voa);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            gl::BufferData(gl::ARRAY_BUFFER, (vertices.len() * mem::size_of::<GLfloat>()) as isize, vertices.as_ptr() as *const c_void, gl::STREAM_DRAW);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
+            gl::BufferData(gl::ELEMENT_ARRAY_BUFFER, (indices.len() * mem::size_of::<u32>()) as isize, indices.as_ptr() as *const c_void, gl::STREAM_DRAW);
+
+            gl::ActiveTexture(gl::TEXTURE0);
+            with_text_shader(|shader| {
+                shader.bind();
+                shader.set_uniform_matrix4("projection", port.get_data());
+                shader.set_uniform_matrix3("transform", Matrix3::identity().getData());
+                shader.set_uniform_color("textColor", &Color::White());
+                shader.set_uniform_vector2("offset", 0.0, 0.0);
+                gl::DepthMask(gl::FALSE);
+
+                for (fill, page, offset, count) in ranges{
+                    fill.apply(shader);
+                    GlyphAtlas::with(|atlas| {
+                        shader.set_uniform_int("distanceField", (atlas.get_page_mode(page) == TextRenderMode::DistanceField) as i32);
+                        shader.set_uniform_int("colorGlyphs", atlas.is_color_page(page) as i32);
+                        atlas.bind_page(page);
+                    });
+                    gl::DrawElements(gl::TRIANGLES, count as GLsizei, gl::UNSIGNED_INT, (offset * mem::size_of::<u32>()) as *const c_void);
+                }
+            });
+            gl::DepthMask(gl::TRUE);
+            gl::BindVertexArray(0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+    }
+
+    // Number of draw calls issued by the last end()
+    pub fn get_draw_calls(&self)->usize{ self.draw_calls }
+}
+
+impl Disposable for TextBatch{
+    fn dispose(&mut self) {
+        self.pages.clear();
+        unsafe{
+            gl::DeleteBuffers(1, &self.vbo);
+            gl::DeleteBuffers(1, &self.ebo);
+            gl::DeleteVertexArrays(1, &self.voa);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use crate::grafx::materials::shader::Shader;
+use crate::grafx::physics::Color;
+
+// Most gradient stops the text shader can blend between; further stops are ignored
+pub const MAX_GRADIENT_STOPS:usize = 8;
+
+// Texture unit the image of a texture fill is bound to, the glyph atlas uses unit 0
+const FILL_TEXTURE_UNIT:u32 = 1;
+
+// How the glyphs of a Text are painted. Positions are relative to the text's bounding box, from (0, 0)
+// at its bottom left to (1, 1) at its top right, so a fill follows the text when it is moved or rotated.
+// Gradient stops are (offset, color) pairs with offsets from 0 to 1, in increasing order.
+// The text color still tints every fill.
+#[allow(dead_code)]
+#[derive(Clone)]
+pub enum TextFill{
+    // the text color alone
+    Solid,
+    LinearGradient{ start:(f32, f32), end:(f32, f32), stops:Vec<(f32, Color)> },
+    // the radius is relative to the box too, so on a wide text the circle becomes an ellipse
+    RadialGradient{ center:(f32, f32), radius:f32, stops:Vec<(f32, Color)> },
+    // a texture (see materials::texture::Texture::get_id) stretched over the box
+    Texture(u32),
+}
+
+#[allow(dead_code)]
+impl TextFill{
+    pub fn is_solid(&self)->bool{ matches!(self, TextFill::Solid) }
+
+    // Sets the fill uniforms of the text shader, binding the image of a texture fill
+    pub(crate) unsafe fn apply(&self, shader:&Shader){
+        match self {
+            TextFill::Solid => shader.set_uniform_int("fillType", 0),
+            TextFill::LinearGradient{ start, end, stops } => {
+                shader.set_uniform_int("fillType", 1);
+                shader.set_uniform_vector2("fillStart", start.0, start.1);
+                shader.set_uniform_vector2("fillEnd", end.0, end.1);
+                TextFill::apply_stops(shader, stops);
+            },
+            TextFill::RadialGradient{ center, radius, stops } => {
+                shader.set_uniform_int("fillType", 2);
+                shader.set_uniform_vector2("fillStart", center.0, center.1);
+                shader.set_uniform_vector2("fillEnd", *radius, 0.0);
+                TextFill::apply_stops(shader, stops);
+            },
+            TextFill::Texture(texture) => {
+                shader.set_uniform_int("fillType", 3);
+                shader.set_uniform_int("fillTexture", FILL_TEXTURE_UNIT as i32);
+                gl::ActiveTexture(gl::TEXTURE0 + FILL_TEXTURE_UNIT);
+                gl::BindTexture(gl::TEXTURE_2D, *texture);
+                gl::ActiveTexture(gl::TEXTURE0);
+            },
+        }
+    }
+
+    unsafe fn apply_stops(shader:&Shader, stops:&[(f32, Color)]){
+        let stops = &stops[..stops.len().min(MAX_GRADIENT_STOPS)];
+        shader.set_uniform_int("stopCount", stops.len() as i32);
+        for (index, (offset, color)) in stops.iter().enumerate(){
+            shader.set_uniform_value(&format!("stopOffsets[{}]", index), *offset);
+            shader.set_uniform_color(&format!("stopColors[{}]", index), color);
+        }
+    }
+}
@@ -0,0 +1,408 @@
+use std::error::Error;
+use std::fmt::{ Display, Formatter };
+use std::{ fs, io, ptr };
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::collections::HashMap;
+use freetype::{ Face, Library };
+use freetype::face::KerningMode;
+use freetype::freetype_sys::{ FT_Error, FT_Face, FT_Fixed, FT_Library, FT_Select_Size, FT_String, FT_UInt, FT_ULong };
+use std::ffi::{ c_void, CStr };
+
+// Animated axes would otherwise make a new instance (and rasterize every glyph again) each frame, so
+// coordinates are rounded to this many steps across an axis
+const VARIATION_STEPS:f32 = 64.0;
+
+// The MM/var API of FreeType, which freetype-sys does not declare
+#[repr(C)]
+struct FT_Var_Axis{ name:*mut FT_String, minimum:FT_Fixed, def:FT_Fixed, maximum:FT_Fixed, tag:FT_ULong, strid:FT_UInt }
+
+#[repr(C)]
+struct FT_MM_Var{ num_axis:FT_UInt, num_designs:FT_UInt, num_namedstyles:FT_UInt, axis:*mut FT_Var_Axis, namedstyle:*mut c_void }
+
+extern "C" {
+    fn FT_Get_MM_Var(face:FT_Face, amaster:*mut *mut FT_MM_Var)->FT_Error;
+    fn FT_Done_MM_Var(library:FT_Library, amaster:*mut FT_MM_Var)->FT_Error;
+    fn FT_Set_Var_Design_Coordinates(face:FT_Face, num_coords:FT_UInt, coords:*mut FT_Fixed)->FT_Error;
+}
+
+// Fallback font compiled into the binary so Text works without any font file next to the executable
+const DEFAULT_FONT:&[u8] = include_bytes!("../../../assets/fonts/DejaVuSansMono.ttf");
+
+thread_local!{
+    static FONT_MANAGER:RefCell<FontManager> = RefCell::new(FontManager::new());
+}
+
+#[derive(Debug)]
+pub enum FontError{ Io(io::Error), FreeType(freetype::Error) }
+
+impl Display for FontError{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FontError::Io(error) => write!(f, "could not read font: {}", error),
+            FontError::FreeType(error) => write!(f, "could not load font: {}", error),
+        }
+    }
+}
+
+impl Error for FontError{}
+
+impl From<io::Error> for FontError{
+    fn from(error: io::Error) -> Self { FontError::Io(error) }
+}
+
+impl From<freetype::Error> for FontError{
+    fn from(error: freetype::Error) -> Self { FontError::FreeType(error) }
+}
+
+// Vertical metrics of a font at one pixel size, in pixels. The descender is negative (below the baseline),
+// and the line gap is the extra space the font puts between the descender of a line and the next ascender.
+// The underline and strikeout positions are the top of their stroke, relative to the baseline.
+#[derive(Clone, Copy, Debug)]
+pub struct FontMetrics{
+    pub ascender:f32, pub descender:f32, pub line_gap:f32, pub line_height:f32,
+    pub underline_position:f32, pub underline_thickness:f32, pub strikeout_position:f32, pub strikeout_thickness:f32
+}
+
+// A design axis of a variable font, such as wght (weight), wdth (width) or slnt (slant), in the units
+// of the font's design space
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub struct FontAxis{ pub tag:String, pub name:String, pub minimum:f32, pub default:f32, pub maximum:f32 }
+
+// A position on one axis of a variable font
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FontVariation{ tag:[u8; 4], value:f32 }
+
+#[allow(dead_code)]
+impl FontVariation{
+    pub fn new(tag:&str, value:f32)->Self{
+        let mut bytes = [b' '; 4];
+        for (byte, value) in bytes.iter_mut().zip(tag.bytes()){
+            *byte = value;
+        }
+        FontVariation{ tag:bytes, value }
+    }
+
+    pub fn get_tag(&self)->&str{ std::str::from_utf8(&self.tag).unwrap_or("") }
+    pub(crate) fn get_tag_bytes(&self)->&[u8; 4]{ &self.tag }
+    pub fn get_value(&self)->f32{ self.value }
+}
+
+// Cheap handle to a face owned by the FontManager
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Font{ id:usize }
+
+pub struct FontManager{
+    library:Library, faces:Vec<Face>, data:Vec<Rc<Vec<u8>>>, default:Option<Font>,
+    names:HashMap<String, Font>, variants:HashMap<(Font, bool, bool), Font>, fallbacks:HashMap<Font, Vec<Font>>,
+    synthetic:HashMap<Font, (bool, bool)>, axes:HashMap<Font, Vec<FontAxis>>, instances:HashMap<(Font, Vec<FT_Fixed>), Font>,
+    coordinates:HashMap<Font, Vec<FontVariation>>, metrics:HashMap<(Font, u32), FontMetrics>
+}
+
+#[allow(dead_code)]
+impl FontManager{
+    fn new()->Self{
+        FontManager{
+            library:Library::init().expect("FreeType could not be initialized"), faces:Vec::new(), data:Vec::new(), default:None,
+            names:HashMap::new(), variants:HashMap::new(), fallbacks:HashMap::new(), synthetic:HashMap::new(),
+            axes:HashMap::new(), instances:HashMap::new(), coordinates:HashMap::new(), metrics:HashMap::new()
+        }
+    }
+
+    // Runs f with the manager of this thread. FreeType faces are not shared between threads, so neither
+    // are the fonts; f must not reach the manager again, which would panic.
+    pub fn with<R>(f:impl FnOnce(&mut FontManager)->R)->R{
+        FONT_MANAGER.with(|manager| f(&mut manager.borrow_mut()))
+    }
+
+    pub fn load_file(&mut self, path:&str)->Result<Font, FontError>{
+        let data = fs::read(path)?;
+        self.add_face(data)
+    }
+
+    pub fn load_bytes(&mut self, data:&[u8])->Result<Font, FontError>{
+        self.add_face(data.to_vec())
+    }
+
+    fn add_face(&mut self, data:Vec<u8>)->Result<Font, FontError>{
+        self.share_face(Rc::new(data))
+    }
+
+    // The raw file is kept next to the FreeType face so the shaper can read its OpenType tables. Faces
+    // made from the same file (synthesized styles) share it.
+    fn share_face(&mut self, data:Rc<Vec<u8>>)->Result<Font, FontError>{
+        let face = self.library.new_memory_face(data.clone(), 0)?;
+        self.faces.push(face);
+        self.data.push(data);
+        Ok(Font{ id:self.faces.len() - 1 })
+    }
+
+    // The bundled font, loaded the first time it is asked for
+    pub fn default_font(&mut self)->Font{
+        if let Some(font) = self.default {
+            return font;
+        }
+        let font = self.load_bytes(DEFAULT_FONT).expect("bundled default font is corrupt");
+        self.default = Some(font);
+        font
+    }
+
+    // Names a font so markup can refer to it with [font=name]
+    pub fn set_name(&mut self, font:Font, name:&str){
+        self.names.insert(String::from(name), font);
+    }
+
+    pub fn find(&self, name:&str)->Option<Font>{ self.names.get(name).cloned() }
+
+    // Registers the font drawn in place of a regular one when bold and/or italic text is asked for
+    pub fn set_variant(&mut self, regular:Font, bold:bool, italic:bool, variant:Font){
+        self.variants.insert((regular, bold, italic), variant);
+    }
+
+    // Names a family and registers the faces it has; the styles it lacks are synthesized when asked for
+    pub fn set_family(&mut self, name:&str, regular:Font, bold:Option<Font>, italic:Option<Font>, bold_italic:Option<Font>){
+        self.set_name(regular, name);
+        for (variant, is_bold, is_italic) in [(bold, true, false), (italic, false, true), (bold_italic, true, true)]{
+            if let Some(variant) = variant {
+                self.set_variant(regular, is_bold, is_italic, variant);
+            }
+        }
+    }
+
+    // The registered variant of a font. A missing one is synthesized from the closest registered face,
+    // its outlines emboldened and/or slanted when they are rasterized.
+    pub fn get_variant(&mut self, font:Font, bold:bool, italic:bool)->Font{
+        if !bold && !italic {
+            return font;
+        }
+        if let Some(variant) = self.variants.get(&(font, bold, italic)) {
+            return *variant;
+        }
+
+        let registered = |manager:&FontManager, bold:bool, italic:bool| manager.variants.get(&(font, bold, italic)).cloned();
+        let (base, embolden, oblique) = match (registered(self, true, false), registered(self, false, true)) {
+            (Some(bold_face), _) if bold && italic => (bold_face, false, true),
+            (_, Some(italic_face)) if bold && italic => (italic_face, true, false),
+            _ => (font, bold, italic),
+        };
+        let (base_embolden, base_oblique) = self.get_synthesis(base);
+        let variant = match self.share_face(self.data[base.id].clone()) {
+            Ok(variant) => variant,
+            Err(_) => return font,
+        };
+        self.synthetic.insert(variant, (embolden || base_embolden, oblique || base_oblique));
+        self.variants.insert((font, bold, italic), variant);
+        variant
+    }
+
+    // Whether the glyphs of a font are emboldened and whether they are slanted when rasterized
+    pub fn get_synthesis(&self, font:Font)->(bool, bool){
+        self.synthetic.get(&font).cloned().unwrap_or((false, false))
+    }
+
+    // How much wider, in pixels, synthetic bold makes every glyph; FreeType grows them by 1/24 em
+    pub fn get_embolden_strength(&self, font:Font, size:u32)->f32{
+        if self.get_synthesis(font).0 { size as f32 / 24.0 } else { 0.0 }
+    }
+
+    // A family and style, as "DroidSans", "DroidSans, bold" or "DroidSans, bold italic". Returns the
+    // family's regular face and whether bold and italic were asked for.
+    pub fn parse_style(&self, description:&str)->Option<(Font, bool, bool)>{
+        let (family, style) = description.split_once(',').unwrap_or((description, ""));
+        let font = self.find(family.trim())?;
+        let (mut bold, mut italic) = (false, false);
+        for word in style.split_whitespace(){
+            match word.to_ascii_lowercase().as_str() {
+                "bold" => bold = true,
+                "italic" | "oblique" => italic = true,
+                "regular" | "normal" => (),
+                _ => return None,
+            }
+        }
+        Some((font, bold, italic))
+    }
+
+    // The face of a family in a style (see parse_style), synthesized when the family does not have it
+    pub fn select(&mut self, description:&str)->Option<Font>{
+        let (font, bold, italic) = self.parse_style(description)?;
+        Some(self.get_variant(font, bold, italic))
+    }
+
+    // Fonts tried in order for the characters a font has no glyph for, e.g. a CJK font and then a symbol font
+    pub fn set_fallbacks(&mut self, font:Font, fallbacks:&[Font]){
+        self.fallbacks.insert(font, fallbacks.to_vec());
+    }
+
+    pub fn get_fallbacks(&self, font:Font)->&[Font]{
+        self.fallbacks.get(&font).map_or(&[], |fallbacks| fallbacks.as_slice())
+    }
+
+    pub fn has_glyph(&self, font:Font, ch:char)->bool{
+        self.get_face(font).get_char_index(ch as usize) != 0
+    }
+
+    // The font a grapheme cluster is drawn with: the first of the font and its fallbacks that has a glyph
+    // for all of its characters, or the font itself when none has. Joiners and variation selectors are
+    // left out, as fonts often draw them without a glyph of their own.
+    pub fn get_fallback(&self, font:Font, cluster:&str)->Font{
+        let covers = |candidate:Font| cluster.chars()
+            .filter(|ch| !ch.is_control() && !matches!(ch, '\u{200c}' | '\u{200d}' | '\u{fe00}'..='\u{fe0f}'))
+            .all(|ch| self.has_glyph(candidate, ch));
+        if covers(font) {
+            return font;
+        }
+        self.get_fallbacks(font).iter().cloned().find(|fallback| covers(*fallback)).unwrap_or(font)
+    }
+
+    // Sets the face to a pixel size and returns how much its bitmaps must be scaled to match it. Color
+    // bitmap fonts only come in a few fixed sizes (strikes), so the closest larger one is picked.
+    pub fn set_size(&self, font:Font, size:u32)->Option<f32>{
+        let face = self.get_face(font);
+        if face.is_scalable() || !face.has_fixed_sizes() {
+            return face.set_pixel_sizes(size, 0).ok().map(|_| 1.0);
+        }
+
+        let raw = face.raw();
+        let strikes = unsafe{ std::slice::from_raw_parts(raw.available_sizes, raw.num_fixed_sizes as usize) };
+        let sizes:Vec<f32> = strikes.iter().map(|strike| (strike.y_ppem >> 6) as f32).collect();
+        let target = size as f32;
+        let index = (0..sizes.len()).filter(|i| sizes[*i] >= target).min_by(|a, b| sizes[*a].total_cmp(&sizes[*b]))
+            .or_else(|| (0..sizes.len()).max_by(|a, b| sizes[*a].total_cmp(&sizes[*b])))?;
+        let error = unsafe{ FT_Select_Size(raw as *const _ as *mut _, index as i32) };
+        if error != 0 || sizes[index] <= 0.0 {
+            return None;
+        }
+        Some(target / sizes[index])
+    }
+
+    // The design axes of a variable font, none for any other font
+    pub fn get_axes(&mut self, font:Font)->&[FontAxis]{
+        if !self.axes.contains_key(&font) {
+            let axes = self.read_axes(font);
+            self.axes.insert(font, axes);
+        }
+        &self.axes[&font]
+    }
+
+    fn read_axes(&self, font:Font)->Vec<FontAxis>{
+        let fixed = |value:FT_Fixed| value as f32 / 65536.0;
+        let face = self.get_face(font).raw() as *const _ as FT_Face;
+        let mut master:*mut FT_MM_Var = ptr::null_mut();
+        unsafe{
+            if FT_Get_MM_Var(face, &mut master) != 0 || master.is_null() {
+                return Vec::new();
+            }
+            let axes = std::slice::from_raw_parts((*master).axis, (*master).num_axis as usize).iter().map(|axis| {
+                let tag = String::from_utf8_lossy(&(axis.tag as u32).to_be_bytes()).into_owned();
+                let name = if axis.name.is_null() { tag.clone() } else { CStr::from_ptr(axis.name).to_string_lossy().into_owned() };
+                FontAxis{ tag, name, minimum:fixed(axis.minimum), default:fixed(axis.def), maximum:fixed(axis.maximum) }
+            }).collect();
+            FT_Done_MM_Var(self.library.raw(), master);
+            axes
+        }
+    }
+
+    // The font at a position of its design space, as a handle of its own so its glyphs are cached apart.
+    // Axes left out keep their default; values are clamped to the axis and rounded to VARIATION_STEPS.
+    // A font without axes is returned as it is.
+    pub fn get_instance(&mut self, font:Font, variations:&[FontVariation])->Font{
+        if variations.is_empty() {
+            return font;
+        }
+        let axes = self.get_axes(font).to_vec();
+        if axes.is_empty() {
+            return font;
+        }
+
+        let values:Vec<f32> = axes.iter().map(|axis| {
+            let value = variations.iter().rev().find(|variation| variation.get_tag() == axis.tag).map_or(axis.default, |variation| variation.value);
+            // steps are counted from the default, so the default itself is always exact
+            let step = (axis.maximum - axis.minimum) / VARIATION_STEPS;
+            let value = if step > 0.0 { axis.default + ((value - axis.default) / step).round() * step } else { value };
+            value.clamp(axis.minimum, axis.maximum)
+        }).collect();
+        let mut coordinates:Vec<FT_Fixed> = values.iter().map(|value| (value * 65536.0).round() as FT_Fixed).collect();
+        if let Some(instance) = self.instances.get(&(font, coordinates.clone())) {
+            return *instance;
+        }
+
+        let instance = match self.share_face(self.data[font.id].clone()) {
+            Ok(instance) => instance,
+            Err(_) => return font,
+        };
+        let face = self.get_face(instance).raw() as *const _ as FT_Face;
+        unsafe{ FT_Set_Var_Design_Coordinates(face, coordinates.len() as FT_UInt, coordinates.as_mut_ptr()); }
+
+        // the shaper reads the same coordinates, and a synthesized style stays synthesized
+        let variations = axes.iter().zip(&values).map(|(axis, value)| FontVariation::new(&axis.tag, *value)).collect();
+        self.coordinates.insert(instance, variations);
+        self.synthetic.insert(instance, self.get_synthesis(font));
+        self.axes.insert(instance, axes);
+        self.instances.insert((font, coordinates), instance);
+        instance
+    }
+
+    // Coordinates of every axis of an instance made by get_instance, empty for any other font
+    pub fn get_variations(&self, font:Font)->&[FontVariation]{
+        self.coordinates.get(&font).map_or(&[], |variations| variations.as_slice())
+    }
+
+    pub fn get_face(&self, font:Font)->&Face{ &self.faces[font.id] }
+    pub fn get_data(&self, font:Font)->&[u8]{ &self.data[font.id] }
+    // The file of a font, kept alive without the manager, for reading its tables after letting go of it
+    pub(crate) fn get_shared_data(&self, font:Font)->Rc<Vec<u8>>{ self.data[font.id].clone() }
+
+    // Every layout measures its lines, so the metrics of a size are read from the font only once
+    pub fn get_metrics(&mut self, font:Font, size:u32)->FontMetrics{
+        if let Some(metrics) = self.metrics.get(&(font, size)) {
+            return *metrics;
+        }
+        let metrics = self.read_metrics(font, size);
+        self.metrics.insert((font, size), metrics);
+        metrics
+    }
+
+    fn read_metrics(&self, font:Font, size:u32)->FontMetrics{
+        let face = self.get_face(font);
+        let (ascender, descender, line_height) = match (self.set_size(font, size), face.size_metrics()) {
+            (Some(scale), Some(metrics)) => (
+                ((metrics.ascender >> 6) as f32 * scale).round(), ((metrics.descender >> 6) as f32 * scale).round(), ((metrics.height >> 6) as f32 * scale).round()
+            ),
+            _ => (size as f32, 0.0, size as f32),
+        };
+
+        // decorations are at least a pixel thick so they never disappear at small sizes
+        let scale = size as f32 / face.em_size().max(1) as f32;
+        let underline_thickness = (face.underline_thickness() as f32 * scale).max(1.0);
+        let underline_position = match face.underline_position() {
+            0 => size as f32 / -10.0,
+            position => position as f32 * scale,
+        };
+        // FreeType does not expose the OS/2 table, so the strikeout is read with the shaper's parser
+        let strikeout = rustybuzz::ttf_parser::Face::parse(self.get_data(font), 0).ok().and_then(|face| face.strikeout_metrics());
+        let (strikeout_position, strikeout_thickness) = match strikeout {
+            Some(metrics) => (metrics.position as f32 * scale, (metrics.thickness as f32 * scale).max(1.0)),
+            None => (ascender * 0.35, underline_thickness),
+        };
+
+        let line_gap = (line_height - ascender + descender).max(0.0);
+        FontMetrics{ ascender, descender, line_gap, line_height, underline_position, underline_thickness, strikeout_position, strikeout_thickness }
+    }
+
+    // Horizontal adjustment in pixels to apply between two glyphs, from the font's kern table
+    pub fn get_kerning(&self, font:Font, size:u32, left:u32, right:u32)->f32{
+        let face = self.get_face(font);
+        if !face.has_kerning() || left == 0 || right == 0 {
+            return 0.0;
+        }
+        let scale = match self.set_size(font, size) {
+            Some(scale) => scale,
+            None => return 0.0,
+        };
+        match face.get_kerning(left, right, KerningMode::KerningDefault) {
+            Ok(kerning) => (kerning.x >> 6) as f32 * scale,
+            Err(_) => 0.0,
+        }
+    }
+}
@@ -0,0 +1,455 @@
+use crate::grafx::Disposable;
+use crate::grafx::physics::{ Color, Vector2, Viewport };
+use crate::grafx::text::{ with_text_shader, Text, TextFill, QUAD_VERTEX_SIZE };
+use crate::grafx::text::atlas::{ GlyphAtlas, TextRenderMode };
+use crate::grafx::text::font::FontManager;
+use crate::grafx::text::layout::TextLayout;
+use crate::grafx::text::span::SpanStyle;
+use gl::types::{ GLfloat, GLsizei };
+use glutin::event::{ ElementState, Ime, KeyboardInput, ModifiersState, VirtualKeyCode, WindowEvent };
+use unicode_segmentation::UnicodeSegmentation;
+use std::ffi::c_void;
+use std::{ mem, ptr };
+
+// Seconds the caret stays visible, and then hidden, while it blinks
+const BLINK_INTERVAL:f32 = 0.5;
+// Width of the caret in pixels of the text's own space
+const CARET_WIDTH:f32 = 2.0;
+
+// An editable Text. The caret and the anchor are byte indices of the text on grapheme boundaries and
+// everything between them is selected. Keys move the caret by characters, words and lines; typed
+// characters, including the text an input method commits, replace the selection. While an input method
+// composes, its text is shown underlined in place of the selection with the cursor it reports.
+pub struct TextInput{
+    voa:u32, vbo:u32, ebo:u32, text:Box<Text>, value:String, preedit:Option<(String, Option<(usize, usize)>)>, caret:usize, anchor:usize,
+    column:Option<f32>, blink:f32, focused:bool, multiline:bool, modifiers:ModifiersState, caret_color:Box<Color>, selection_color:Box<Color>
+}
+
+#[allow(dead_code)]
+impl TextInput{
+    pub fn new(text:&str)->Self{
+        let (voa, vbo, ebo) = unsafe{
+            let mut voa = 0;
+            let mut vbo = 0;
+            let mut ebo = 0;
+            gl::GenVertexArrays(1, &mut voa);
+            gl::GenBuffers(1, &mut vbo);
+            gl::GenBuffers(1, &mut ebo);
+            gl::BindVertexArray(voa);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
+
+            // the caret and the selection are quads in the same format as the glyphs of a Text
+            let stride = (QUAD_VERTEX_SIZE * mem::size_of::<GLfloat>()) as GLsizei;
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(0, 4, gl::FLOAT, gl::FALSE, stride, ptr::null());
+            gl::EnableVertexAttribArray(3);
+            gl::VertexAttribPointer(3, 2, gl::FLOAT, gl::FALSE, stride, (4 * mem::size_of::<GLfloat>()) as *const c_void);
+            gl::EnableVertexAttribArray(1);
+            gl::VertexAttribPointer(1, 4, gl::FLOAT, gl::FALSE, stride, (6 * mem::size_of::<GLfloat>()) as *const c_void);
+            gl::BindVertexArray(0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+            (voa, vbo, ebo)
+        };
+
+        TextInput{
+            voa, vbo, ebo, text:Box::new(Text::new(text)), value:String::from(text), preedit:None, caret:text.len(), anchor:text.len(),
+            column:None, blink:0.0, focused:false, multiline:false, modifiers:ModifiersState::empty(),
+            caret_color:Box::new(Color::Black()), selection_color:Box::new(Color::new(0.2, 0.45, 0.9, 0.4))
+        }
+    }
+
+    // The Text being edited, for its font, colors, style and transform. Its content should be changed
+    // through set_text, so the caret stays inside it.
+    pub fn get_label(&mut self)->&mut Text{ &mut self.text }
+    pub fn get_text(&self)->&str{ &self.value }
+    // The text an input method is composing, which is not part of the input's text until it is committed
+    pub fn get_preedit(&self)->Option<&str>{ self.preedit.as_ref().map(|(preedit, _)| preedit.as_str()) }
+
+    // Replaces the whole text and moves the caret to its end
+    pub fn set_text(&mut self, data:&str){
+        self.value = String::from(data);
+        self.preedit = None;
+        self.show();
+        self.set_caret(data.len(), false);
+    }
+
+    pub fn set_focused(&mut self, focused:bool){
+        self.focused = focused;
+        self.blink = 0.0;
+        if !focused && self.preedit.take().is_some() {
+            self.show();
+        }
+    }
+
+    // Whether Enter starts a new line; a single line input turns pasted line breaks into spaces
+    pub fn set_multiline(&mut self, multiline:bool){ self.multiline = multiline; }
+    pub fn set_caret_color(&mut self, color:Color){ *self.caret_color = color; }
+    pub fn set_selection_color(&mut self, color:Color){ *self.selection_color = color; }
+
+    pub fn is_focused(&self)->bool{ self.focused }
+    pub fn is_multiline(&self)->bool{ self.multiline }
+    pub fn get_caret(&self)->usize{ self.caret }
+
+    // The selected byte range, start first whichever way it was selected
+    pub fn get_selection(&self)->(usize, usize){ (self.caret.min(self.anchor), self.caret.max(self.anchor)) }
+    pub fn get_selected_text(&self)->&str{
+        let (start, end) = self.get_selection();
+        &self.value[start..end]
+    }
+
+    // Moves the caret to a byte index, snapped back to a character boundary. With extend the anchor
+    // stays where it is and the selection grows or shrinks, otherwise the selection is dropped.
+    pub fn set_caret(&mut self, index:usize, extend:bool){
+        let text = &self.value;
+        let mut index = index.min(text.len());
+        while !text.is_char_boundary(index) {
+            index -= 1;
+        }
+        self.caret = index;
+        if !extend {
+            self.anchor = index;
+        }
+        self.column = None;
+        self.blink = 0.0;
+    }
+
+    pub fn select_all(&mut self){
+        self.anchor = 0;
+        self.set_caret(self.value.len(), true);
+    }
+
+    // Puts the caret at the character edge nearest to a point in world space, e.g. where the mouse was
+    // clicked. Returns false when the point is not on the text.
+    pub fn place_caret(&mut self, point:&Vector2, extend:bool)->bool{
+        match self.text.hit_test(point) {
+            Some((index, leading)) => {
+                let index = if leading { index } else { self.next_grapheme(index) };
+                self.set_caret(index, extend);
+                true
+            },
+            None => false,
+        }
+    }
+
+    // Replaces the selection with the given text and puts the caret after it
+    pub fn insert(&mut self, data:&str){
+        let (value, caret) = TextInput::replace(&self.value, self.get_selection(), data, self.multiline);
+        self.value = value;
+        self.show();
+        self.set_caret(caret, false);
+    }
+
+    // Puts the text in the label, with the composition in place of the selection
+    fn show(&mut self){
+        match &self.preedit {
+            Some((preedit, _)) => {
+                let (start, _) = self.get_selection();
+                let (value, end) = TextInput::replace(&self.value, self.get_selection(), preedit, true);
+                let mut style = SpanStyle::new();
+                style.set_underline(true);
+                self.text.set_text(&value);
+                self.text.add_span(start, end, style);
+            },
+            None => self.text.set_text(&self.value),
+        }
+    }
+
+    // The text with a byte range replaced, and the index right after the replacement
+    fn replace(text:&str, (start, end):(usize, usize), data:&str, multiline:bool)->(String, usize){
+        let data = if multiline { data.to_string() } else { data.replace(TextLayout::is_line_break, " ") };
+        let mut value = text.to_string();
+        value.replace_range(start..end, &data);
+        (value, start + data.len())
+    }
+
+    // Takes the keyboard, character and input method events of the window while the input has the
+    // focus. Returns whether the event changed the input. Keys go to the input method while it composes.
+    pub fn handle_event(&mut self, event:&WindowEvent)->bool{
+        match event {
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = *modifiers;
+                false
+            },
+            // keys released while the window was in the background never report it
+            WindowEvent::Focused(false) => {
+                self.modifiers = ModifiersState::empty();
+                false
+            },
+            _ if !self.focused => false,
+            WindowEvent::Ime(Ime::Preedit(preedit, cursor)) => {
+                self.preedit = if preedit.is_empty() { None } else { Some((preedit.clone(), *cursor)) };
+                self.blink = 0.0;
+                self.show();
+                true
+            },
+            WindowEvent::Ime(Ime::Commit(data)) => {
+                self.preedit = None;
+                self.insert(data);
+                true
+            },
+            WindowEvent::Ime(Ime::Disabled) => {
+                self.preedit = None;
+                self.show();
+                true
+            },
+            _ if self.preedit.is_some() => false,
+            WindowEvent::ReceivedCharacter(ch) => {
+                // backspace, enter and the shortcuts come as control characters too, they are handled as keys
+                if ch.is_control() {
+                    return false;
+                }
+                self.insert(ch.encode_utf8(&mut [0; 4]));
+                true
+            },
+            WindowEvent::KeyboardInput{ input:KeyboardInput{ state:ElementState::Pressed, virtual_keycode:Some(key), .. }, .. } => self.handle_key(*key),
+            _ => false,
+        }
+    }
+
+    fn handle_key(&mut self, key:VirtualKeyCode)->bool{
+        // Ctrl moves by words, as Alt (Option) does on macOS
+        let (shift, word) = (self.modifiers.shift(), self.modifiers.ctrl() || self.modifiers.alt());
+        let (start, end) = self.get_selection();
+        let caret = self.caret;
+
+        match key {
+            // without shift an arrow first collapses the selection to its side
+            VirtualKeyCode::Left => {
+                let index = if start != end && !shift { start } else if word { self.previous_word(caret) } else { self.previous_grapheme(caret) };
+                self.set_caret(index, shift);
+            },
+            VirtualKeyCode::Right => {
+                let index = if start != end && !shift { end } else if word { self.next_word(caret) } else { self.next_grapheme(caret) };
+                self.set_caret(index, shift);
+            },
+            // the caret keeps the column it started from while it moves through shorter lines
+            VirtualKeyCode::Up | VirtualKeyCode::Down => {
+                let column = self.column.unwrap_or_else(|| self.text.get_layout().caret_position(caret).0);
+                let index = self.vertical(caret, key == VirtualKeyCode::Down, column);
+                self.set_caret(index, shift);
+                self.column = Some(column);
+            },
+            VirtualKeyCode::Home => {
+                let index = if word { 0 } else { self.line_start(caret) };
+                self.set_caret(index, shift);
+            },
+            VirtualKeyCode::End => {
+                let index = if word { self.text.get_text().len() } else { self.line_end(caret) };
+                self.set_caret(index, shift);
+            },
+            VirtualKeyCode::Back => {
+                if start == end {
+                    self.anchor = if word { self.previous_word(caret) } else { self.previous_grapheme(caret) };
+                }
+                self.insert("");
+            },
+            VirtualKeyCode::Delete => {
+                if start == end {
+                    self.anchor = if word { self.next_word(caret) } else { self.next_grapheme(caret) };
+                }
+                self.insert("");
+            },
+            VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter if self.multiline => self.insert("\n"),
+            VirtualKeyCode::A if self.modifiers.ctrl() || self.modifiers.logo() => self.select_all(),
+            _ => return false,
+        }
+        true
+    }
+
+    fn previous_grapheme(&self, index:usize)->usize{
+        self.text.get_text()[..index].grapheme_indices(true).next_back().map_or(0, |(start, _)| start)
+    }
+
+    fn next_grapheme(&self, index:usize)->usize{
+        self.text.get_text()[index..].graphemes(true).next().map_or(index, |grapheme| index + grapheme.len())
+    }
+
+    // Start of the word before the index, skipping the spaces and punctuation in between
+    fn previous_word(&self, index:usize)->usize{
+        let text = &self.text.get_text()[..index];
+        text.split_word_bound_indices().rev().find(|(_, word)| word.chars().any(char::is_alphanumeric)).map_or(0, |(start, _)| start)
+    }
+
+    // End of the word after the index, skipping the spaces and punctuation in between
+    fn next_word(&self, index:usize)->usize{
+        let text = self.text.get_text();
+        text[index..].split_word_bound_indices().find(|(_, word)| word.chars().any(char::is_alphanumeric))
+            .map_or(text.len(), |(start, word)| index + start + word.len())
+    }
+
+    fn line_start(&self, index:usize)->usize{ self.text.get_layout().get_line_range(index).0 }
+
+    // The end of the line before its line break, or before the spaces a wrapped line ends with, as an
+    // index right after them would already be on the next line
+    fn line_end(&self, index:usize)->usize{
+        let text = self.text.get_text();
+        let (start, end) = self.text.get_layout().get_line_range(index);
+        let line = &text[start..end];
+        let content = if line.ends_with(TextLayout::is_line_break) {
+            line.trim_end_matches(TextLayout::is_line_break)
+        }else if end < text.len() {
+            line.trim_end()
+        }else{
+            line
+        };
+        start + content.len()
+    }
+
+    // The index on the line above or below whose caret is closest to the column
+    fn vertical(&self, index:usize, down:bool, column:f32)->usize{
+        let layout = self.text.get_layout();
+        let (start, end) = layout.get_line_range(index);
+        let target = match down {
+            true if end >= self.text.get_text().len() => return self.text.get_text().len(),
+            true => end,
+            false if start == 0 => return 0,
+            false => self.previous_grapheme(start),
+        };
+        let (line_start, line_end) = (self.line_start(target), self.line_end(target));
+
+        let text = &self.text.get_text()[line_start..line_end];
+        let boundaries = Some(line_start).into_iter().chain(text.grapheme_indices(true).map(|(offset, grapheme)| line_start + offset + grapheme.len()));
+        boundaries.min_by(|a, b| {
+            let distance = |index:usize| (layout.caret_position(index).0 - column).abs();
+            distance(*a).total_cmp(&distance(*b))
+        }).unwrap_or(line_start)
+    }
+
+    // Advances the blink; moving the caret restarts it, so it is never hidden right after a key press
+    pub fn update(&mut self, delta:f32){
+        self.blink += delta;
+    }
+
+    // Where the caret is in world space, for the window to open an input method's candidates next to it
+    pub fn get_ime_position(&self)->(f32, f32){
+        let (caret, _) = self.get_shown_caret();
+        let position = self.text.caret_position(caret.unwrap_or(self.caret));
+        (position.getX(), position.getY())
+    }
+
+    // Byte indices of the label's text where the caret is drawn and the range highlighted: the caret and
+    // the selection, or while composing the cursor and the range the input method reports in its text,
+    // with no caret when it hides the cursor
+    fn get_shown_caret(&self)->(Option<usize>, (usize, usize)){
+        match &self.preedit {
+            Some((_, Some((cursor_start, cursor_end)))) => {
+                let (start, _) = self.get_selection();
+                (Some(start + cursor_end), (start + cursor_start.min(cursor_end), start + cursor_start.max(cursor_end)))
+            },
+            Some((_, None)) => (None, (0, 0)),
+            None => (Some(self.caret), self.get_selection()),
+        }
+    }
+
+    // Quads of the selection, behind the text, and of the caret in front of it
+    fn build_quads(&self)->(Vec<f32>, Vec<u32>, usize){
+        let layout = self.text.get_layout();
+        let mut boxes:Vec<([f32; 4], &Color)> = Vec::new();
+        let (caret, (start, end)) = self.get_shown_caret();
+        if start != end {
+            boxes.extend(layout.selection_bounds(start, end).into_iter().map(|bounds| (bounds, self.selection_color.as_ref())));
+        }
+        let selection = boxes.len();
+
+        if let Some(caret) = caret.filter(|_| self.focused && self.blink % (2.0 * BLINK_INTERVAL) < BLINK_INTERVAL) {
+            let (x, y) = layout.caret_position(caret);
+            let metrics = FontManager::with(|manager| manager.get_metrics(self.text.get_font(), self.text.get_font_size()));
+            let half = CARET_WIDTH / 2.0;
+            boxes.push(([x - half, y + metrics.descender, x + half, y + metrics.ascender], self.caret_color.as_ref()));
+        }
+
+        let solid = GlyphAtlas::with(|atlas| atlas.get_solid(TextRenderMode::Bitmap));
+        let [uv_left, uv_top, uv_right, uv_bottom] = *solid.get_uv();
+        let mut vertices = Vec::with_capacity(boxes.len() * 4 * QUAD_VERTEX_SIZE);
+        let mut indices = Vec::with_capacity(boxes.len() * 6);
+        for ([left, bottom, right, top], color) in boxes{
+            let Color{ red, green, blue, alpha } = *color;
+            let base = (vertices.len() / QUAD_VERTEX_SIZE) as u32;
+            vertices.extend_from_slice(&[
+                left,  top,       uv_left,  uv_top,       0.0, 0.0,    red, green, blue, alpha,
+                left,  bottom,    uv_left,  uv_bottom,    0.0, 0.0,    red, green, blue, alpha,
+                right, bottom,    uv_right, uv_bottom,    0.0, 0.0,    red, green, blue, alpha,
+                right, top,       uv_right, uv_top,       0.0, 0.0,    red, green, blue, alpha
+            ]);
+            indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+        (vertices, indices, selection * 6)
+    }
+
+    unsafe fn draw_quads(&self, port:&Viewport, offset:usize, count:usize){
+        if count == 0 { return; }
+        gl::BindVertexArray(self.voa);
+        gl::ActiveTexture(gl::TEXTURE0);
+        with_text_shader(|shader| {
+            shader.bind();
+            shader.set_uniform_matrix4("projection", port.get_data());
+            shader.set_uniform_matrix3("transform", self.text.transform.getTransformMatrix());
+            shader.set_uniform_color("textColor", &Color::White());
+            shader.set_uniform_vector2("offset", 0.0, 0.0);
+            shader.set_uniform_int("distanceField", 0);
+            shader.set_uniform_int("colorGlyphs", 0);
+            TextFill::Solid.apply(shader);
+        });
+        gl::VertexAttrib3f(2, 0.5, 0.0, 1.0);
+
+        GlyphAtlas::with(|atlas| {
+            let page = atlas.get_solid(TextRenderMode::Bitmap).get_page();
+            atlas.bind_page(page);
+        });
+        gl::DepthMask(gl::FALSE);
+        gl::DrawElements(gl::TRIANGLES, count as GLsizei, gl::UNSIGNED_INT, (offset * mem::size_of::<u32>()) as *const c_void);
+        gl::DepthMask(gl::TRUE);
+        gl::BindVertexArray(0);
+        gl::BindTexture(gl::TEXTURE_2D, 0);
+    }
+
+    pub fn draw(&self, port:&Viewport){
+        let (vertices, indices, selection) = self.build_quads();
+        unsafe{
+            gl::BindVertexArray(self.voa);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            gl::BufferData(gl::ARRAY_BUFFER, (vertices.len() * mem::size_of::<GLfloat>()) as isize, vertices.as_ptr() as *const c_void, gl::STREAM_DRAW);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
+            gl::BufferData(gl::ELEMENT_ARRAY_BUFFER, (indices.len() * mem::size_of::<u32>()) as isize, indices.as_ptr() as *const c_void, gl::STREAM_DRAW);
+            gl::BindVertexArray(0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+
+            self.draw_quads(port, 0, selection);
+            self.text.draw(port);
+            self.draw_quads(port, selection, indices.len() - selection);
+        }
+    }
+}
+
+impl Disposable for TextInput{
+    fn dispose(&mut self) {
+        self.text.dispose();
+        unsafe{
+            gl::DeleteBuffers(1, &self.vbo);
+            gl::DeleteBuffers(1, &self.ebo);
+            gl::DeleteVertexArrays(1, &self.voa);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn typing_a_space_moves_the_caret(){
+        let font = FontManager::with(|manager| manager.default_font());
+        let (value, caret) = TextInput::replace("ab", (2, 2), " ", false);
+        assert_eq!((value.as_str(), caret), ("ab ", 3));
+        let layout = TextLayout::new(&value, font);
+        assert!(layout.caret_position(caret).0 > layout.caret_position(2).0);
+    }
+
+    #[test]
+    fn single_line_input_turns_line_breaks_into_spaces(){
+        assert_eq!(TextInput::replace("ab", (0, 2), "x\r\ny", false), (String::from("x  y"), 4));
+        assert_eq!(TextInput::replace("ab", (1, 1), "\n", true), (String::from("a\nb"), 2));
+    }
+}
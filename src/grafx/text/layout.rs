@@ -0,0 +1,969 @@
+use crate::grafx::physics::Color;
+use crate::grafx::text::atlas::{ GlyphAtlas, TextRenderMode, DISTANCE_FIELD_SIZE, DISTANCE_FIELD_SPREAD };
+use crate::grafx::text::font::{ Font, FontManager, FontVariation };
+use crate::grafx::text::markup::parse_markup;
+use crate::grafx::text::shaping::{ shape, FontFeature, ShapedGlyph };
+use crate::grafx::text::span::{ SpanStyle, TextSpan };
+use crate::grafx::utils::Character;
+use unicode_linebreak::linebreaks;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_bidi::{ Level, ParagraphBidiInfo };
+use std::collections::HashSet;
+use std::mem;
+
+// A tab is as wide as this many spaces
+const TAB_SIZE:f32 = 4.0;
+
+// Start and End follow the direction of each paragraph: Start is Left for left-to-right text and Right for right-to-left text
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HorizontalAlign{ Left, Center, Right, Start, End }
+
+impl HorizontalAlign{
+    fn resolve(self, rtl:bool)->HorizontalAlign{
+        match (self, rtl) {
+            (HorizontalAlign::Start, false) | (HorizontalAlign::End, true) => HorizontalAlign::Left,
+            (HorizontalAlign::Start, true) | (HorizontalAlign::End, false) => HorizontalAlign::Right,
+            (align, _) => align,
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VerticalAlign{ Top, Middle, Baseline, Bottom }
+
+// Base direction of the paragraphs; Auto takes it from the first strong character of each paragraph (UAX #9)
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TextDirection{ Auto, LeftToRight, RightToLeft }
+
+// A glyph placed on a line. The glyph is its index in the font, the cluster the byte offset in the text
+// of the character it draws, the level its bidirectional embedding level. Font and size are the ones of
+// the span it is in, or the fallback font that had its glyph.
+#[derive(Clone)]
+struct LineGlyph{ character:Character, glyph:u32, cluster:usize, level:u8, advance:f32, offset:(f32, f32), font:Font, size:u32 }
+
+// A paragraph as the last layout shaped it, its glyphs in logical order. Shaping is the slow part of a
+// layout, so when the text changes only the paragraphs that are not kept here are shaped again.
+struct ShapedParagraph{ text:String, start:usize, glyphs:Vec<LineGlyph>, rtl:bool }
+
+impl ShapedParagraph{
+    // The same paragraph after the text before it changed length
+    fn moved_to(&self, start:usize)->ShapedParagraph{
+        let glyphs = self.glyphs.iter().map(|glyph| LineGlyph{ cluster:glyph.cluster + start - self.start, ..glyph.clone() }).collect();
+        ShapedParagraph{ text:self.text.clone(), start, glyphs, rtl:self.rtl }
+    }
+}
+
+// Glyphs of one line in visual order, the direction of the paragraph the line belongs to, the bytes
+// of the text the line holds (trailing whitespace and the line break included) and the advance of the
+// whitespace glyphs at its logical end, which hang past its edge instead of counting in its width
+struct TextLine{ glyphs:Vec<LineGlyph>, rtl:bool, start:usize, end:usize, trailing:f32 }
+// A glyph, underline or strikethrough ready to be drawn: its rectangle in the text's own space (y up,
+// the origin at the text's position), the area of the atlas page it samples, and the color of its span
+#[derive(Clone, Copy)]
+pub struct GlyphQuad{ pub page:usize, pub position:(f32, f32), pub size:(f32, f32), pub uv:[f32; 4], pub color:Option<Color> }
+
+// Measurements of a laid out text, in pixels. Ascent, descent and line gap are the largest of the fonts
+// used (the descent is negative), the advance width is the pen advance of the widest line. The bounds are
+// the box the lines are laid out in, from the fonts' ascent and descent; the ink bounds tightly enclose
+// the pixels actually drawn, so they include the overhang of glyphs like "f" and the tail of "g".
+// Both boxes are left, bottom, right, top around the text's origin.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TextMetrics{
+    pub ascent:f32, pub descent:f32, pub line_gap:f32, pub line_height:f32, pub advance_width:f32, pub line_count:usize,
+    pub bounds:[f32; 4], pub ink_bounds:[f32; 4]
+}
+
+impl TextMetrics{
+    pub fn get_ink_width(&self)->f32{ self.ink_bounds[2] - self.ink_bounds[0] }
+    pub fn get_ink_height(&self)->f32{ self.ink_bounds[3] - self.ink_bounds[1] }
+}
+
+// Everything about a string that does not need OpenGL: shaping, line breaking, alignment, metrics and
+// the quads of the glyphs. Glyph bitmaps are added to the atlas on the CPU and only uploaded when a page
+// is drawn, so a layout can be built and measured without a context.
+pub struct TextLayout{
+    text:String, spans:Vec<TextSpan>, font:Font, font_size:u32, render_mode:TextRenderMode, direction:TextDirection,
+    horizontal_align:HorizontalAlign, vertical_align:VerticalAlign, line_spacing:f32, max_width:Option<f32>, kerning:bool, features:Vec<FontFeature>,
+    variations:Vec<FontVariation>, shaped:Vec<ShapedParagraph>, lines:Vec<TextLine>, origins:Vec<(f32, f32)>, offsets:Vec<Vec<f32>>, extents:Vec<(f32, f32)>, metrics:TextMetrics, quads:Vec<GlyphQuad>
+}
+
+#[allow(dead_code)]
+impl TextLayout{
+    // A layout with the defaults of Text: 16 pixels, centered on the origin, one line spacing, no wrapping
+    pub fn new(text:&str, font:Font)->Self{
+        let mut init = TextLayout{
+            text:String::from(text), spans:Vec::new(), font, font_size:16, render_mode:TextRenderMode::Bitmap, direction:TextDirection::Auto,
+            horizontal_align:HorizontalAlign::Center, vertical_align:VerticalAlign::Middle, line_spacing:1.0, max_width:None, kerning:true, features:Vec::new(),
+            variations:Vec::new(), shaped:Vec::new(), lines:Vec::new(), origins:Vec::new(), offsets:Vec::new(), extents:Vec::new(), metrics:TextMetrics::default(), quads:Vec::new()
+        };
+        init.refresh();
+        init
+    }
+
+    // Features handed to the shaper; kerning is the kern feature unless it was set explicitly
+    fn get_shaping_features(&self)->Vec<FontFeature>{
+        let mut features = self.features.clone();
+        if !self.kerning && !features.iter().any(|feature| feature.get_tag() == "kern") {
+            features.push(FontFeature::new("kern", false));
+        }
+        features
+    }
+
+    // Shapes one paragraph (text without hard breaks) into glyphs in logical order. The paragraph is split
+    // into runs of one bidirectional level first, so every run is shaped in its own direction. Tabs
+    // become wide spaces. Characters the font has no glyph for are drawn with its fallback fonts, and
+    // show U+FFFD or the notdef glyph when none of them has one either.
+    fn shape_paragraph(&self, paragraph:&str, offset:usize)->(Vec<LineGlyph>, bool){
+        let default_level = match self.direction {
+            TextDirection::Auto => None,
+            TextDirection::LeftToRight => Some(Level::ltr()),
+            TextDirection::RightToLeft => Some(Level::rtl()),
+        };
+        let bidi = ParagraphBidiInfo::new(paragraph, default_level);
+        let features = self.get_shaping_features();
+
+        // fonts only change between grapheme clusters, so a base and its marks always share one
+        let clusters:HashSet<usize> = paragraph.grapheme_indices(true).map(|(index, _)| index).collect();
+        let mut glyphs = Vec::new();
+        let mut start = 0;
+        while start < paragraph.len(){
+            // a run also ends where a span or a fallback changes the font or size
+            let level = bidi.levels[start];
+            let (font, size) = self.get_run_font(offset + start);
+            let mut end = start;
+            while end < paragraph.len() && bidi.levels[end] == level && (!clusters.contains(&end) || self.get_run_font(offset + end) == (font, size)) {
+                end += 1;
+            }
+            for shaped in self.shape_run(&paragraph[start..end], offset + start, level.is_rtl(), &features, font, size){
+                if let Some(glyph) = self.get_line_glyph(shaped, level.number(), font, size) {
+                    glyphs.push(glyph);
+                }
+            }
+            start = end;
+        }
+        (glyphs, bidi.paragraph_level.is_rtl())
+    }
+
+    fn shape_run(&self, run:&str, offset:usize, rtl:bool, features:&[FontFeature], font:Font, size:u32)->Vec<ShapedGlyph>{
+        // the shaper reads the font's own advances, which synthetic bold makes wider
+        let strength = FontManager::with(|manager| manager.get_embolden_strength(font, size));
+        let shaped = shape(font, size, run, offset, rtl, features).map(|mut glyphs| {
+            for glyph in glyphs.iter_mut().filter(|glyph| glyph.x_advance > 0.0){
+                glyph.x_advance += strength;
+            }
+            glyphs
+        });
+        shaped.unwrap_or_else(|| {
+            // the shaper could not read the font, so every character gets its own glyph from FreeType, kerned
+            // with its neighbour by the font's kern table
+            let mut glyphs:Vec<ShapedGlyph> = Vec::new();
+            for (index, ch) in run.char_indices(){
+                let glyph = GlyphAtlas::get_glyph_index(font, ch);
+                let mut advance = self.get_advance(&GlyphAtlas::with(|atlas| atlas.get_glyph(font, size, glyph, self.render_mode)), size);
+                if let Some(previous) = glyphs.last_mut().filter(|_| self.kerning) {
+                    // the pair is kerned in visual order, where a right-to-left glyph comes before the previous one
+                    if rtl {
+                        advance += FontManager::with(|manager| manager.get_kerning(font, size, glyph, previous.glyph));
+                    }else{
+                        previous.x_advance += FontManager::with(|manager| manager.get_kerning(font, size, previous.glyph, glyph));
+                    }
+                }
+                glyphs.push(ShapedGlyph{ glyph, cluster:offset + index, x_advance:advance, x_offset:0.0, y_offset:0.0 });
+            }
+            glyphs
+        })
+    }
+
+    fn get_line_glyph(&self, shaped:ShapedGlyph, level:u8, font:Font, size:u32)->Option<LineGlyph>{
+        let ch = self.text[shaped.cluster..].chars().next().unwrap_or(' ');
+        if TextLayout::is_line_break(ch) {
+            return None;
+        }
+        // a tab is a wide space, so it is whitespace for wrapping and measuring like a space
+        if ch == '\t' {
+            let glyph = GlyphAtlas::get_glyph_index(font, ' ');
+            let character = GlyphAtlas::with(|atlas| atlas.get_glyph(font, size, glyph, self.render_mode));
+            let space = self.shape_run(" ", 0, false, &[], font, size).first().map_or(0.0, |shaped| shaped.x_advance);
+            let advance = space * TAB_SIZE;
+            return Some(LineGlyph{ character, glyph, cluster:shaped.cluster, level, advance, offset:(0.0, 0.0), font, size });
+        }
+        if shaped.glyph == 0 {
+            let glyph = GlyphAtlas::get_glyph_index(font, ch);
+            let character = GlyphAtlas::with(|atlas| atlas.get_glyph(font, size, glyph, self.render_mode));
+            let advance = self.get_advance(&character, size);
+            return Some(LineGlyph{ character, glyph, cluster:shaped.cluster, level, advance, offset:(0.0, 0.0), font, size });
+        }
+        let character = GlyphAtlas::with(|atlas| atlas.get_glyph(font, size, shaped.glyph, self.render_mode));
+        Some(LineGlyph{ character, glyph:shaped.glyph, cluster:shaped.cluster, level, advance:shaped.x_advance, offset:(shaped.x_offset, shaped.y_offset), font, size })
+    }
+
+    // The style at a byte of the text, from every span covering it
+    fn get_span_style(&self, index:usize)->SpanStyle{
+        self.spans.iter().filter(|span| span.contains(index)).fold(SpanStyle::new(), |style, span| style.merge(span.get_style()))
+    }
+
+    // Font and size the grapheme cluster at a byte of the text is drawn with: the span's font, or the
+    // first of its fallbacks that has the cluster's glyphs, in its bold or italic variant and at the
+    // text's position on its design axes
+    fn get_run_font(&self, index:usize)->(Font, u32){
+        let cluster = self.text[index..].graphemes(true).next().unwrap_or("");
+        if self.spans.is_empty() {
+            return FontManager::with(|manager| {
+                let font = manager.get_fallback(self.font, cluster);
+                (manager.get_instance(font, &self.variations), self.font_size)
+            });
+        }
+        let style = self.get_span_style(index);
+        FontManager::with(|manager| {
+            let font = manager.get_fallback(style.get_font().unwrap_or(self.font), cluster);
+            let font = manager.get_variant(font, style.is_bold(), style.is_italic());
+            (manager.get_instance(font, &self.variations), style.get_size().unwrap_or(self.font_size))
+        })
+    }
+
+    // Font size relative to the size glyphs were rasterized at, which differ for distance fields
+    pub(crate) fn get_glyph_scale(&self, size:u32)->f32{
+        match self.render_mode {
+            TextRenderMode::Bitmap => 1.0,
+            TextRenderMode::DistanceField => size as f32 / DISTANCE_FIELD_SIZE as f32,
+        }
+    }
+
+    fn get_advance(&self, character:&Character, size:u32)->f32{
+        (character.get_advance().x >> 6) as f32 * self.get_glyph_scale(size)
+    }
+
+    // Puts the glyphs of a line in visual order (rule L2 of UAX #9): from the highest level down to the
+    // lowest odd one, every sequence of glyphs at that level or above is reversed
+    fn reorder(line:&mut [LineGlyph]){
+        let highest = line.iter().map(|glyph| glyph.level).max().unwrap_or(0);
+        let lowest_odd = match line.iter().map(|glyph| glyph.level).filter(|level| level % 2 == 1).min() {
+            Some(level) => level,
+            None => return,
+        };
+        for level in (lowest_odd..=highest).rev(){
+            let mut i = 0;
+            while i < line.len(){
+                if line[i].level < level {
+                    i += 1;
+                    continue;
+                }
+                let start = i;
+                while i < line.len() && line[i].level >= level {
+                    i += 1;
+                }
+                line[start..i].reverse();
+            }
+        }
+    }
+
+    // Byte ranges of the paragraphs between hard line breaks (a CR LF pair counts as one break)
+    fn get_paragraphs(&self)->Vec<(usize, usize)>{
+        let mut paragraphs = Vec::new();
+        let mut start = 0;
+        let mut characters = self.text.char_indices().peekable();
+        while let Some((index, ch)) = characters.next(){
+            if TextLayout::is_line_break(ch) {
+                paragraphs.push((start, index));
+                if ch == '\r' && characters.peek().is_some_and(|(_, next)| *next == '\n') {
+                    characters.next();
+                }
+                start = characters.peek().map_or(self.text.len(), |(next, _)| *next);
+            }
+        }
+        paragraphs.push((start, self.text.len()));
+        paragraphs
+    }
+
+    // Splits the text into lines at hard breaks and, when a maximum width is set, at the line break
+    // opportunities of UAX #14. A word wider than the maximum is broken between grapheme clusters instead.
+    // Each paragraph is shaped as a whole, so ligatures and kerning are kept across the wrapped lines.
+    fn get_lines(&self)->Vec<TextLine>{
+        let is_whitespace = |glyph:&LineGlyph| self.text[glyph.cluster..].chars().next().is_some_and(char::is_whitespace);
+        let mut lines = Vec::new();
+
+        for (index, shaped) in self.shaped.iter().enumerate(){
+            let (paragraph, start, glyphs, rtl) = (shaped.text.as_str(), shaped.start, &shaped.glyphs, shaped.rtl);
+            // the last line of a paragraph holds the line break after it too
+            let end = self.shaped.get(index + 1).map_or(self.text.len(), |next| next.start);
+            let mut paragraph_lines:Vec<Vec<LineGlyph>> = vec![Vec::new()];
+            let mut width = 0.0;
+            let mut first = 0;
+
+            for (offset, _) in linebreaks(paragraph){
+                let mut last = first;
+                while last < glyphs.len() && glyphs[last].cluster < start + offset {
+                    last += 1;
+                }
+                let segment = &glyphs[first..last];
+                first = last;
+                let full:f32 = segment.iter().map(|glyph| glyph.advance).sum();
+                let trailing:f32 = segment.iter().rev().take_while(|glyph| is_whitespace(glyph)).map(|glyph| glyph.advance).sum();
+
+                if let Some(max_width) = self.max_width {
+                    if width + full - trailing > max_width && width > 0.0 {
+                        paragraph_lines.push(Vec::new());
+                        width = 0.0;
+                    }
+                    if full - trailing > max_width {
+                        let clusters:HashSet<usize> = paragraph.grapheme_indices(true).map(|(index, _)| start + index).collect();
+                        for (i, glyph) in segment.iter().enumerate(){
+                            let boundary = clusters.contains(&glyph.cluster) && (i == 0 || segment[i - 1].cluster != glyph.cluster);
+                            if width + glyph.advance > max_width && width > 0.0 && boundary && !is_whitespace(glyph) {
+                                paragraph_lines.push(Vec::new());
+                                width = 0.0;
+                            }
+                            width += glyph.advance;
+                            paragraph_lines.last_mut().unwrap().push(glyph.clone());
+                        }
+                        continue;
+                    }
+                }
+
+                width += full;
+                paragraph_lines.last_mut().unwrap().extend_from_slice(segment);
+            }
+
+            let starts:Vec<usize> = paragraph_lines.iter().map(|line| line.iter().map(|glyph| glyph.cluster).min().unwrap_or(start)).collect();
+            let count = paragraph_lines.len();
+            for (i, mut line) in paragraph_lines.into_iter().enumerate(){
+                // the whitespace a line is wrapped at is not drawn on either line; at the end of a paragraph
+                // it stays, so a caret can be placed after it
+                if i + 1 < count {
+                    while line.last().is_some_and(is_whitespace) {
+                        line.pop();
+                    }
+                }
+                let trailing = line.iter().rev().take_while(|glyph| is_whitespace(glyph)).map(|glyph| glyph.advance).sum();
+                TextLayout::reorder(&mut line);
+                lines.push(TextLine{ glyphs:line, rtl, start:starts[i], end:starts.get(i + 1).cloned().unwrap_or(end), trailing });
+            }
+        }
+        lines
+    }
+
+    pub(crate) fn is_line_break(ch:char)->bool{
+        matches!(ch, '\n' | '\r' | '\u{0B}' | '\u{0C}' | '\u{85}' | '\u{2028}' | '\u{2029}')
+    }
+
+    // Lays the text out again, shaping only the paragraphs that are not in the cache. Spans style bytes
+    // by their offset, so with spans a paragraph is only reused where it was.
+    fn refresh(&mut self){
+        let previous = mem::take(&mut self.shaped);
+        self.shaped = self.get_paragraphs().into_iter().map(|(start, end)| {
+            let paragraph = &self.text[start..end];
+            match previous.iter().find(|cached| cached.text == paragraph && (self.spans.is_empty() || cached.start == start)) {
+                Some(cached) => cached.moved_to(start),
+                None => {
+                    let (glyphs, rtl) = self.shape_paragraph(paragraph, start);
+                    ShapedParagraph{ text:String::from(paragraph), start, glyphs, rtl }
+                },
+            }
+        }).collect();
+        self.lines = self.get_lines();
+        self.arrange();
+    }
+
+    // Lays the text out again after a change to how it is shaped (font, size, spans...), which makes
+    // every cached paragraph stale
+    fn reshape(&mut self){
+        self.shaped.clear();
+        self.refresh();
+    }
+
+    // Places the lines again without shaping or breaking them, for changes that only move them
+    fn arrange(&mut self){
+        self.position_lines();
+        self.build_quads();
+    }
+
+    // Ascender, descender, line height and line gap of a line: the largest of the fonts and sizes on it
+    fn get_line_metrics(&self, line:&TextLine)->(f32, f32, f32, f32){
+        let mut used:Vec<(Font, u32)> = line.glyphs.iter().map(|glyph| (glyph.font, glyph.size)).collect();
+        used.dedup();
+        if used.is_empty() {
+            used.push((self.font, self.font_size));
+        }
+        used.iter().map(|(font, size)| FontManager::with(|manager| manager.get_metrics(*font, *size))).fold((f32::MIN, f32::MAX, 0.0, 0.0), |(ascender, descender, height, gap), metrics| {
+            (ascender.max(metrics.ascender), descender.min(metrics.descender), f32::max(height, metrics.line_height), f32::max(gap, metrics.line_gap))
+        })
+    }
+
+    // Places the pen origin of every line. Lines are spaced by the line height of their fonts, each one
+    // is aligned inside the block following its paragraph's direction, and the block itself is aligned
+    // around the text's position following the direction of the first paragraph.
+    fn position_lines(&mut self){
+        // baselines relative to the first one; the space between two lines is the descent of the upper
+        // one and the height of the lower one without its descent, which is one line height for a single font
+        let metrics:Vec<(f32, f32, f32, f32)> = self.lines.iter().map(|line| self.get_line_metrics(line)).collect();
+        let mut baselines = Vec::with_capacity(metrics.len());
+        let mut baseline = 0.0;
+        for (i, (_, descender, height, _)) in metrics.iter().enumerate(){
+            if i > 0 {
+                baseline -= (metrics[i - 1].1.abs() + height + descender) * self.line_spacing;
+            }
+            baselines.push(baseline);
+        }
+        let empty = self.get_line_metrics(&TextLine{ glyphs:Vec::new(), rtl:false, start:0, end:0, trailing:0.0 });
+        let (first, last) = (metrics.first().cloned().unwrap_or(empty), metrics.last().cloned().unwrap_or(empty));
+
+        let (offsets, widths):(Vec<Vec<f32>>, Vec<f32>) = self.lines.iter().map(|line| {
+            let mut x = 0.0;
+            let offsets = line.glyphs.iter().map(|glyph| { x += glyph.advance; x - glyph.advance }).collect();
+            (offsets, x - line.trailing)
+        }).unzip();
+        let width = widths.iter().cloned().fold(0.0, f32::max);
+
+        let top = first.0;
+        let bottom = last.1 + baselines.last().cloned().unwrap_or(0.0);
+        let rtl = self.lines.first().is_some_and(|line| line.rtl);
+        let left = match self.horizontal_align.resolve(rtl) {
+            HorizontalAlign::Center => - width / 2.0,
+            HorizontalAlign::Right => - width,
+            _ => 0.0,
+        };
+        let offset = match self.vertical_align {
+            VerticalAlign::Top => - top,
+            VerticalAlign::Middle => - (top + bottom) / 2.0,
+            VerticalAlign::Baseline => 0.0,
+            VerticalAlign::Bottom => - bottom,
+        };
+
+        self.origins = widths.iter().zip(&self.lines).enumerate().map(|(i, (line_width, line))| {
+            let x = match self.horizontal_align.resolve(line.rtl) {
+                HorizontalAlign::Center => left + (width - line_width) / 2.0,
+                HorizontalAlign::Right => left + width - line_width,
+                _ => left,
+            };
+            // the trailing whitespace of a right-to-left line is on its left, outside of its width
+            let hanging = if line.rtl { line.trailing } else { 0.0 };
+            (x - hanging, offset + baselines[i])
+        }).collect();
+        self.offsets = offsets;
+        self.extents = metrics.iter().zip(&baselines).map(|(line, baseline)| (offset + baseline + line.0, offset + baseline + line.1)).collect();
+
+        let largest = metrics.iter().fold(empty, |(ascender, descender, height, gap), line| {
+            (ascender.max(line.0), descender.min(line.1), height.max(line.2), gap.max(line.3))
+        });
+        self.metrics = TextMetrics{
+            ascent:largest.0, descent:largest.1, line_height:largest.2, line_gap:largest.3, advance_width:width, line_count:self.lines.len(),
+            bounds:[left, bottom + offset, left + width, top + offset], ink_bounds:[0.0; 4]
+        };
+    }
+
+    // One quad per visible glyph, underline and strikethrough, sorted by atlas page
+    fn build_quads(&mut self){
+        let solid = GlyphAtlas::with(|atlas| atlas.get_solid(self.render_mode));
+        // distance field glyphs carry the spread around their outline, which is not ink
+        let spread = match self.render_mode {
+            TextRenderMode::Bitmap => 0.0,
+            TextRenderMode::DistanceField => DISTANCE_FIELD_SPREAD as f32,
+        };
+        let mut ink:Option<[f32; 4]> = None;
+        let mut add_ink = |left:f32, bottom:f32, right:f32, top:f32| {
+            ink = Some(match ink {
+                Some([ink_left, ink_bottom, ink_right, ink_top]) => [ink_left.min(left), ink_bottom.min(bottom), ink_right.max(right), ink_top.max(top)],
+                None => [left, bottom, right, top],
+            });
+        };
+        let mut quads = Vec::new();
+        for ((line, offsets), (x, y)) in self.lines.iter().zip(&self.offsets).zip(self.origins.iter().cloned()){
+            for (glyph, offset) in line.glyphs.iter().zip(offsets){
+                let style = self.get_span_style(glyph.cluster);
+                let color = style.get_color().cloned();
+                let ch = &glyph.character;
+                let scale = self.get_glyph_scale(glyph.size);
+                // shaped advances are fractional, bitmaps are kept on whole pixels so they stay sharp
+                let xpos = (x + offset + glyph.offset.0).round() + ch.get_bearing().0 as f32 * scale;
+                let ypos = (y + glyph.offset.1).round() - (ch.get_size().get_height() - ch.get_bearing().1) as f32 * scale;
+                let width = ch.get_size().get_width() as f32 * scale;
+                let height = ch.get_size().get_height() as f32 * scale;
+                if width > 0.0 && height > 0.0 {
+                    quads.push(GlyphQuad{ page:ch.get_page(), position:(xpos, ypos), size:(width, height), uv:*ch.get_uv(), color });
+                    let padding = spread * scale;
+                    add_ink(xpos + padding, ypos + padding, xpos + width - padding, ypos + height - padding);
+                }
+
+                // decorations span the glyph's whole advance, so the ones of neighbouring glyphs join up
+                if style.is_underline() || style.is_strikethrough() {
+                    let font = FontManager::with(|manager| manager.get_metrics(glyph.font, glyph.size));
+                    let mut decorations = Vec::new();
+                    if style.is_underline() { decorations.push((font.underline_position, font.underline_thickness)); }
+                    if style.is_strikethrough() { decorations.push((font.strikeout_position, font.strikeout_thickness)); }
+                    for (position, thickness) in decorations{
+                        let top = (y + position).round();
+                        quads.push(GlyphQuad{ page:solid.get_page(), position:(x + offset, top - thickness), size:(glyph.advance, thickness), uv:*solid.get_uv(), color });
+                        add_ink(x + offset, top - thickness, x + offset + glyph.advance, top);
+                    }
+                }
+            }
+        }
+        quads.sort_by_key(|quad| quad.page);
+
+        self.metrics.ink_bounds = ink.unwrap_or([0.0; 4]);
+        self.quads = quads;
+    }
+
+    // Replaces the text; spans refer to byte ranges of the old text, so they are removed
+    pub fn set_text(&mut self, data:&str){
+        if self.text == data && self.spans.is_empty() {
+            return;
+        }
+        self.text = String::from(data);
+        if self.spans.is_empty() {
+            self.refresh();
+        }else{
+            self.spans.clear();
+            self.reshape();
+        }
+    }
+
+    // Replaces the text with the plain text of the markup and its styled spans, see markup::parse_markup
+    pub fn set_markup(&mut self, markup:&str){
+        let (text, spans) = parse_markup(markup);
+        self.text = text;
+        self.spans = spans;
+        self.reshape();
+    }
+
+    pub fn set_spans(&mut self, spans:Vec<TextSpan>){
+        self.spans = spans;
+        self.reshape();
+    }
+
+    // Styles the bytes start..end of the text, over any span added before
+    pub fn add_span(&mut self, start:usize, end:usize, style:SpanStyle){
+        self.spans.push(TextSpan::new(start, end, style));
+        self.reshape();
+    }
+
+    pub fn set_font_size(&mut self, size:u32){
+        if self.font_size != size {
+            self.font_size = size;
+            self.reshape();
+        }
+    }
+
+    // Distance field glyphs are shared by every font size and stay sharp when the text is scaled or rotated
+    pub fn set_render_mode(&mut self, mode:TextRenderMode){
+        if self.render_mode != mode {
+            self.render_mode = mode;
+            self.reshape();
+        }
+    }
+
+    pub fn set_font(&mut self, font:Font){
+        self.font = font;
+        self.reshape();
+    }
+
+    pub fn set_direction(&mut self, direction:TextDirection){
+        self.direction = direction;
+        self.reshape();
+    }
+
+    pub fn set_alignment(&mut self, horizontal:HorizontalAlign, vertical:VerticalAlign){
+        self.horizontal_align = horizontal;
+        self.vertical_align = vertical;
+        self.arrange();
+    }
+
+    // Distance between baselines as a multiple of the fonts' own line height
+    pub fn set_line_spacing(&mut self, spacing:f32){
+        self.line_spacing = spacing;
+        self.arrange();
+    }
+
+    // Wraps lines that would be wider than the given width, or disables wrapping with None
+    pub fn set_max_width(&mut self, width:Option<f32>){
+        self.max_width = width;
+        self.refresh();
+    }
+
+    // Turns pair kerning from the font on or off
+    pub fn set_kerning(&mut self, enabled:bool){
+        self.kerning = enabled;
+        self.reshape();
+    }
+
+    // Turns an OpenType feature of the font (liga, kern, smcp, tnum...) on or off for this text
+    pub fn set_feature(&mut self, tag:&str, enabled:bool){
+        let feature = FontFeature::new(tag, enabled);
+        self.features.retain(|current| current.get_tag() != feature.get_tag());
+        self.features.push(feature);
+        self.reshape();
+    }
+
+    // Moves every variable font of the text along one of its design axes (wght, wdth, slnt...)
+    pub fn set_variation(&mut self, tag:&str, value:f32){
+        let variation = FontVariation::new(tag, value);
+        self.variations.retain(|current| current.get_tag() != variation.get_tag());
+        self.variations.push(variation);
+        self.reshape();
+    }
+
+    pub fn get_text(&self)->&str{ &self.text }
+    pub fn get_spans(&self)->&[TextSpan]{ &self.spans }
+    pub fn get_font(&self)->Font{ self.font }
+    pub fn get_font_size(&self)->u32{ self.font_size }
+    pub fn get_render_mode(&self)->TextRenderMode{ self.render_mode }
+    pub fn get_direction(&self)->TextDirection{ self.direction }
+    pub fn get_horizontal_align(&self)->HorizontalAlign{ self.horizontal_align }
+    pub fn get_vertical_align(&self)->VerticalAlign{ self.vertical_align }
+    pub fn get_line_spacing(&self)->f32{ self.line_spacing }
+    pub fn get_max_width(&self)->Option<f32>{ self.max_width }
+    pub fn is_kerning(&self)->bool{ self.kerning }
+    pub fn get_features(&self)->&[FontFeature]{ &self.features }
+    pub fn get_variations(&self)->&[FontVariation]{ &self.variations }
+    pub fn get_line_count(&self)->usize{ self.lines.len() }
+    pub fn get_quads(&self)->&[GlyphQuad]{ &self.quads }
+
+    // Font, size and index of every glyph with the point on the baseline its outline starts from, for
+    // drawing the glyphs from their outlines instead of the atlas
+    pub(crate) fn get_glyph_origins(&self)->Vec<(Font, u32, u32, (f32, f32))>{
+        let mut origins = Vec::new();
+        for ((line, offsets), (x, y)) in self.lines.iter().zip(&self.offsets).zip(self.origins.iter().cloned()){
+            for (glyph, offset) in line.glyphs.iter().zip(offsets){
+                origins.push((glyph.font, glyph.size, glyph.glyph, (x + offset + glyph.offset.0, y + glyph.offset.1)));
+            }
+        }
+        origins
+    }
+    pub fn get_metrics(&self)->&TextMetrics{ &self.metrics }
+
+    // The character under a point of the layout's own space, as its byte index in the text and whether
+    // the point is on its leading edge (the side a caret before it is drawn on). None when the point is
+    // outside the layout's bounds or the whitespace hanging past them. The space between two lines is
+    // split halfway between them.
+    pub fn hit_test(&self, x:f32, y:f32)->Option<(usize, bool)>{
+        let [left, bottom, right, top] = self.metrics.bounds;
+        if self.lines.is_empty() || y < bottom || y > top {
+            return None;
+        }
+        let index = self.extents.windows(2).position(|pair| y >= (pair[0].1 + pair[1].0) / 2.0).unwrap_or(self.lines.len() - 1);
+        let (line, (origin_x, _)) = (&self.lines[index], self.origins[index]);
+        let line_right = origin_x + line.glyphs.iter().map(|glyph| glyph.advance).sum::<f32>();
+        if x < left.min(origin_x) || x > right.max(line_right) {
+            return None;
+        }
+
+        for (glyph, offset) in line.glyphs.iter().zip(&self.offsets[index]){
+            let glyph_left = origin_x + offset;
+            if x < glyph_left + glyph.advance {
+                let rtl = glyph.level % 2 == 1;
+                return Some((glyph.cluster, (x < glyph_left + glyph.advance / 2.0) != rtl));
+            }
+        }
+        // past the end of the line, on the right edge of the last glyph drawn
+        match line.glyphs.last() {
+            Some(glyph) => Some((glyph.cluster, glyph.level % 2 == 1)),
+            None => Some((line.start, true)),
+        }
+    }
+
+    // Byte range of the line holding a byte index of the text, the way caret_position assigns indices to lines
+    pub fn get_line_range(&self, index:usize)->(usize, usize){
+        match self.lines.iter().rposition(|line| line.start <= index) {
+            Some(line_index) => (self.lines[line_index].start, self.lines[line_index].end),
+            None => (0, self.text.len()),
+        }
+    }
+
+    // Where a caret before the character at a byte index of the text is drawn, on the line's baseline in
+    // the layout's own space. An index at a wrap goes to the start of the next line, an index past a
+    // line's last character to the end of that line.
+    pub fn caret_position(&self, index:usize)->(f32, f32){
+        let line_index = match self.lines.iter().rposition(|line| line.start <= index) {
+            Some(line_index) => line_index,
+            None => return self.origins.first().cloned().unwrap_or((0.0, 0.0)),
+        };
+        let (line, offsets, (origin_x, origin_y)) = (&self.lines[line_index], &self.offsets[line_index], self.origins[line_index]);
+
+        let glyph = line.glyphs.iter().zip(offsets).filter(|(glyph, _)| glyph.cluster <= index).max_by_key(|(glyph, _)| glyph.cluster);
+        let x = match glyph {
+            // the leading edge is the left one of a left-to-right glyph, the trailing edge the other side
+            Some((glyph, offset)) => {
+                let leading = glyph.cluster == index;
+                let rtl = glyph.level % 2 == 1;
+                origin_x + offset + if leading != rtl { 0.0 } else { glyph.advance }
+            },
+            None if line.rtl => origin_x + line.glyphs.iter().map(|glyph| glyph.advance).sum::<f32>(),
+            None => origin_x,
+        };
+        (x, origin_y)
+    }
+
+    // Boxes covering the characters of the byte range start..end, one per line they are on, as left,
+    // bottom, right, top in the layout's own space. Mixed direction lines get a single box around every
+    // selected glyph rather than one box per run.
+    pub fn selection_bounds(&self, start:usize, end:usize)->Vec<[f32; 4]>{
+        let mut boxes = Vec::new();
+        for (index, line) in self.lines.iter().enumerate(){
+            if line.end <= start || line.start >= end { continue; }
+            let (origin_x, _) = self.origins[index];
+            let (top, bottom) = self.extents[index];
+            let selected = line.glyphs.iter().zip(&self.offsets[index]).filter(|(glyph, _)| glyph.cluster >= start && glyph.cluster < end);
+            let range = selected.fold(None, |range:Option<(f32, f32)>, (glyph, offset)| {
+                let (left, right) = (origin_x + offset, origin_x + offset + glyph.advance);
+                Some(range.map_or((left, right), |(range_left, range_right)| (range_left.min(left), range_right.max(right))))
+            });
+            if let Some((left, right)) = range {
+                boxes.push([left, bottom, right, top]);
+            }
+        }
+        boxes
+    }
+
+    // left, bottom, right, top of the laid out block around the origin
+    pub fn get_bounds(&self)->[f32; 4]{ self.metrics.bounds }
+    pub fn get_width(&self)->f32{ self.metrics.bounds[2] - self.metrics.bounds[0] }
+    pub fn get_height(&self)->f32{ self.metrics.bounds[3] - self.metrics.bounds[1] }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn layout(text:&str)->TextLayout{
+        TextLayout::new(text, FontManager::with(|manager| manager.default_font()))
+    }
+
+    fn wrapped(text:&str, max_width:f32)->TextLayout{
+        let mut init = layout(text);
+        init.set_max_width(Some(max_width));
+        init
+    }
+
+    fn assert_close(actual:f32, expected:f32){
+        assert!((actual - expected).abs() < 0.01, "{} is not {}", actual, expected);
+    }
+
+    #[test]
+    fn lines_wrap_at_the_max_width(){
+        let (short, long) = (layout("aaa").get_width(), layout("aaa bbb").get_width());
+        assert_eq!(layout("aaa bbb ccc").get_line_count(), 1);
+        assert_eq!(wrapped("aaa bbb ccc", long + 1.0).get_line_count(), 2);
+        assert_eq!(wrapped("aaa bbb ccc", short + 1.0).get_line_count(), 3);
+        assert!(wrapped("aaa bbb ccc", short + 1.0).get_width() <= short + 1.0);
+    }
+
+    #[test]
+    fn words_wider_than_the_max_width_break_between_characters(){
+        let three = layout("abc").get_width();
+        let text = wrapped("abcdefghij", three + 1.0);
+        assert_eq!(text.get_line_count(), 4);
+        assert!(text.get_width() <= three + 1.0);
+        assert_eq!(text.get_line_range(3), (3, 6));
+    }
+
+    #[test]
+    fn bounds_follow_the_alignment(){
+        let mut text = layout("abc");
+        let width = text.get_width();
+        text.set_alignment(HorizontalAlign::Left, VerticalAlign::Top);
+        let [left, _, right, top] = text.get_bounds();
+        assert_close(left, 0.0);
+        assert_close(right, width);
+        assert_close(top, 0.0);
+
+        text.set_alignment(HorizontalAlign::Center, VerticalAlign::Middle);
+        let [left, bottom, right, top] = text.get_bounds();
+        assert_close(left, -width / 2.0);
+        assert_close(right, width / 2.0);
+        assert_close(top, -bottom);
+
+        text.set_alignment(HorizontalAlign::Right, VerticalAlign::Bottom);
+        let [left, bottom, right, _] = text.get_bounds();
+        assert_close(left, -width);
+        assert_close(right, 0.0);
+        assert_close(bottom, 0.0);
+
+        text.set_alignment(HorizontalAlign::Left, VerticalAlign::Baseline);
+        let metrics = *text.get_metrics();
+        assert_close(text.get_bounds()[3], metrics.ascent);
+        assert_close(text.get_bounds()[1], metrics.descent);
+    }
+
+    fn on_baseline(text:&str)->TextLayout{
+        let mut init = layout(text);
+        init.set_alignment(HorizontalAlign::Left, VerticalAlign::Baseline);
+        init
+    }
+
+    #[test]
+    fn ink_follows_the_glyphs(){
+        let descending = *on_baseline("gy").get_metrics();
+        let standing = *on_baseline("Ab").get_metrics();
+        assert!(descending.ink_bounds[1] < 0.0);
+        assert!(standing.ink_bounds[1] >= 0.0);
+        assert!(standing.ink_bounds[3] > 0.0 && standing.ink_bounds[3] <= standing.ascent);
+        assert!(standing.get_ink_width() > 0.0 && standing.get_ink_width() < standing.advance_width);
+        assert_close(standing.advance_width, on_baseline("Ab").get_width());
+    }
+
+    #[test]
+    fn line_gap_is_what_the_line_height_leaves(){
+        let metrics = *layout("Ab").get_metrics();
+        assert_close(metrics.line_gap, metrics.line_height - metrics.ascent + metrics.descent);
+        assert!(metrics.line_gap >= 0.0);
+    }
+
+    #[test]
+    fn lines_are_one_line_height_apart(){
+        let one = layout("a");
+        let line_height = one.get_metrics().line_height;
+        let mut three = layout("a\nb\nc");
+        assert_eq!(three.get_line_count(), 3);
+        assert_close(three.get_height(), one.get_height() + 2.0 * line_height);
+        three.set_line_spacing(2.0);
+        assert_close(three.get_height(), one.get_height() + 4.0 * line_height);
+    }
+
+    #[test]
+    fn carriage_return_line_feed_is_one_line_break(){
+        let text = layout("a\r\nb");
+        assert_eq!(text.get_line_count(), 2);
+        assert_close(text.get_height(), layout("a\nb").get_height());
+        assert_eq!(text.get_line_range(0), (0, 3));
+        assert_eq!(text.get_line_range(3), (3, 4));
+        assert_eq!(layout("a\r\rb").get_line_count(), 3);
+        assert_eq!(layout("a\n").get_line_count(), 2);
+    }
+
+    #[test]
+    fn caret_positions_advance_through_a_line(){
+        let text = layout("abc");
+        let positions:Vec<f32> = (0..=3).map(|index| text.caret_position(index).0).collect();
+        assert!(positions.windows(2).all(|pair| pair[1] > pair[0]));
+        assert_close(positions[0], text.get_bounds()[0]);
+        assert_close(positions[3], text.get_bounds()[2]);
+    }
+
+    #[test]
+    fn caret_at_a_wrap_starts_the_next_line(){
+        let short = layout("aaa").get_width();
+        let text = wrapped("aaa bbb", short + 1.0);
+        let (end_x, first) = text.caret_position(3);
+        let (start_x, second) = text.caret_position(4);
+        assert!(second < first);
+        assert!(start_x < end_x);
+        assert_close(text.caret_position(7).1, second);
+    }
+
+    #[test]
+    fn hit_test_finds_the_nearest_edge(){
+        let text = layout("abc");
+        let [left, bottom, right, top] = text.get_bounds();
+        let (_, y) = text.caret_position(0);
+        let advance = text.caret_position(1).0 - text.caret_position(0).0;
+        assert_eq!(text.hit_test(left + advance * 0.25, y), Some((0, true)));
+        assert_eq!(text.hit_test(left + advance * 0.75, y), Some((0, false)));
+        assert_eq!(text.hit_test(left + advance * 1.25, y), Some((1, true)));
+        assert_eq!(text.hit_test(right + 1.0, y), None);
+        assert_eq!(text.hit_test(left, top + 1.0), None);
+        assert_eq!(text.hit_test(left, bottom - 1.0), None);
+
+        // the gap between two lines goes to the nearer one
+        let mut text = layout("a\nb");
+        text.set_line_spacing(3.0);
+        let metrics = *text.get_metrics();
+        let (x, upper) = text.caret_position(0);
+        let (_, lower) = text.caret_position(2);
+        let middle = (upper + metrics.descent + lower + metrics.ascent) / 2.0;
+        assert!(upper + metrics.descent - middle > 1.0);
+        assert_eq!(text.hit_test(x + 0.5, middle + 1.0), Some((0, true)));
+        assert_eq!(text.hit_test(x + 0.5, middle - 1.0), Some((2, true)));
+    }
+
+    #[test]
+    fn hit_test_past_a_short_line_ends_it(){
+        let text = layout("abc\nd");
+        let (_, y) = text.caret_position(4);
+        assert_eq!(text.hit_test(text.get_bounds()[2] - 0.5, y), Some((4, false)));
+    }
+
+    #[test]
+    fn selection_has_a_box_per_line(){
+        let text = layout("ab\ncd");
+        assert_eq!(text.selection_bounds(0, 2).len(), 1);
+        assert_eq!(text.selection_bounds(1, 5).len(), 2);
+        assert!(text.selection_bounds(1, 1).is_empty());
+    }
+
+    #[test]
+    fn trailing_whitespace_moves_the_caret(){
+        let text = layout("hello ");
+        assert!(text.caret_position(6).0 > text.caret_position(5).0);
+        let text = layout("ab ");
+        assert!(text.caret_position(3).0 > text.caret_position(2).0);
+    }
+
+    #[test]
+    fn trailing_whitespace_is_not_measured(){
+        assert_eq!(layout("hello ").get_width(), layout("hello").get_width());
+    }
+
+    #[test]
+    fn trailing_whitespace_can_be_hit(){
+        let text = layout("ab ");
+        let (space, _) = text.caret_position(2);
+        let (end, y) = text.caret_position(3);
+        assert_eq!(text.hit_test((space + end) / 2.0 + 0.5, y + 1.0).map(|(index, _)| index), Some(2));
+    }
+
+    #[test]
+    fn right_to_left_runs_are_reversed(){
+        let text = layout("abc אבג def");
+        let clusters:Vec<usize> = text.lines[0].glyphs.iter().map(|glyph| glyph.cluster).collect();
+        assert_eq!(clusters, vec![0, 1, 2, 3, 8, 6, 4, 10, 11, 12, 13]);
+        let (first, second, third) = (text.caret_position(4).0, text.caret_position(6).0, text.caret_position(8).0);
+        assert!(first > second && second > third);
+    }
+
+    #[test]
+    fn start_and_end_follow_the_paragraph_direction(){
+        let mut text = layout("אבג");
+        assert!(text.lines[0].rtl);
+        text.set_alignment(HorizontalAlign::Start, VerticalAlign::Baseline);
+        assert_close(text.get_bounds()[2], 0.0);
+        text.set_alignment(HorizontalAlign::End, VerticalAlign::Baseline);
+        assert_close(text.get_bounds()[0], 0.0);
+        let mut text = layout("abc");
+        text.set_alignment(HorizontalAlign::Start, VerticalAlign::Baseline);
+        assert_close(text.get_bounds()[0], 0.0);
+    }
+
+    #[test]
+    fn trailing_whitespace_of_right_to_left_text_hangs_on_the_left(){
+        let mut text = layout("אבג ");
+        text.set_alignment(HorizontalAlign::Start, VerticalAlign::Baseline);
+        assert_close(text.get_width(), layout("אבג").get_width());
+        assert_close(text.caret_position(0).0, 0.0);
+        assert_close(text.caret_position(6).0, text.get_bounds()[0]);
+        assert!(text.caret_position(7).0 < text.get_bounds()[0]);
+    }
+
+    #[test]
+    fn tabs_are_wide_spaces(){
+        let space = layout("a b").get_width() - layout("ab").get_width();
+        assert_close(layout("a\tb").get_width(), layout("ab").get_width() + space * TAB_SIZE);
+        let text = layout("a\t");
+        assert_close(text.get_width(), layout("a").get_width());
+        assert!(text.caret_position(2).0 > text.caret_position(1).0);
+    }
+
+    #[test]
+    fn glyphs_larger_than_an_atlas_page_are_laid_out(){
+        let oversized = |text:&TextLayout| text.get_quads().iter().any(|quad| quad.size.1 > 1024.0);
+        let mut text = layout("W");
+        text.set_font_size(1500);
+        assert!(oversized(&text));
+        let mut text = layout("");
+        text.set_markup("[size=1500]W[/size]");
+        assert!(oversized(&text));
+    }
+}
@@ -0,0 +1,147 @@
+use crate::grafx::physics::Color;
+use crate::grafx::text::font::FontManager;
+use crate::grafx::text::span::{ SpanStyle, TextSpan };
+
+// Parses inline markup into plain text and the spans styling it. Supported tags:
+//   [b] [i] [u] [s]           bold, italic, underline, strikethrough
+//   [color=#rgb] ... [color=#rrggbbaa]
+//   [size=24]                 pixel size
+//   [font=name]               a font named with FontManager::set_name, optionally with a style
+//                             as in [font=DroidSans, bold italic]
+// Every tag is closed by [/tag]; unclosed tags run to the end of the text. "[[" is a literal "[", and
+// anything that is not a known tag is kept as text.
+pub fn parse_markup(markup:&str)->(String, Vec<TextSpan>){
+    let mut text = String::with_capacity(markup.len());
+    let mut spans:Vec<TextSpan> = Vec::new();
+    // name of every open tag and the index of the span it started
+    let mut open:Vec<(&str, usize)> = Vec::new();
+
+    let mut rest = markup;
+    while let Some(index) = rest.find('[') {
+        text.push_str(&rest[..index]);
+        rest = &rest[index..];
+        if let Some(escaped) = rest.strip_prefix("[[") {
+            text.push('[');
+            rest = escaped;
+            continue;
+        }
+
+        let tag = match rest.find(']') {
+            Some(end) => &rest[1..end],
+            None => break,
+        };
+        if let Some(name) = tag.strip_prefix('/') {
+            if let Some(position) = open.iter().rposition(|(open_name, _)| *open_name == name) {
+                let (_, span) = open.remove(position);
+                spans[span].set_end(text.len());
+                rest = &rest[tag.len() + 2..];
+                continue;
+            }
+        }else if let Some(style) = parse_tag(tag) {
+            let name = tag.split('=').next().unwrap_or(tag);
+            open.push((name, spans.len()));
+            spans.push(TextSpan::new(text.len(), text.len(), style));
+            rest = &rest[tag.len() + 2..];
+            continue;
+        }
+
+        // not a tag, the bracket is part of the text
+        text.push('[');
+        rest = &rest[1..];
+    }
+    text.push_str(rest);
+
+    for (_, span) in open{
+        spans[span].set_end(text.len());
+    }
+    (text, spans)
+}
+
+fn parse_tag(tag:&str)->Option<SpanStyle>{
+    let mut style = SpanStyle::new();
+    match tag.split_once('=') {
+        None => match tag {
+            "b" => style.set_bold(true),
+            "i" => style.set_italic(true),
+            "u" => style.set_underline(true),
+            "s" => style.set_strikethrough(true),
+            _ => return None,
+        },
+        Some(("color", value)) => style.set_color(parse_color(value)?),
+        Some(("size", value)) => style.set_size(value.trim().parse().ok().filter(|size| *size > 0)?),
+        Some(("font", value)) => {
+            // the style goes on the span, so the face is picked (or synthesized) with any [b] or [i] around it
+            let (font, bold, italic) = FontManager::with(|manager| manager.parse_style(value))?;
+            style.set_font(font);
+            if bold { style.set_bold(true); }
+            if italic { style.set_italic(true); }
+        },
+        _ => return None,
+    }
+    Some(style)
+}
+
+// #rgb, #rgba, #rrggbb or #rrggbbaa
+fn parse_color(value:&str)->Option<Color>{
+    let digits = value.trim().strip_prefix('#')?;
+    if !digits.chars().all(|ch| ch.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channels:Vec<f32> = match digits.len() {
+        3 | 4 => digits.chars().map(|ch| ch.to_digit(16).unwrap() as f32 / 15.0).collect(),
+        6 | 8 => (0..digits.len()).step_by(2).map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap() as f32 / 255.0).collect(),
+        _ => return None,
+    };
+    Some(Color::new(channels[0], channels[1], channels[2], channels.get(3).cloned().unwrap_or(1.0)))
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn ranges(spans:&[TextSpan])->Vec<(usize, usize)>{
+        spans.iter().map(|span| (span.get_start(), span.get_end())).collect()
+    }
+
+    #[test]
+    fn tags_become_spans(){
+        let (text, spans) = parse_markup("[b]bold[/b] and [i]it[u]alic[/u][/i]");
+        assert_eq!(text, "bold and italic");
+        assert_eq!(ranges(&spans), vec![(0, 4), (9, 15), (11, 15)]);
+        assert!(spans[0].get_style().is_bold());
+        assert!(spans[1].get_style().is_italic());
+        assert!(spans[2].get_style().is_underline());
+    }
+
+    #[test]
+    fn values_are_parsed(){
+        let (text, spans) = parse_markup("[color=#f00]red[/color][size=24]big[/size]");
+        assert_eq!(text, "redbig");
+        let color = spans[0].get_style().get_color().cloned().unwrap();
+        assert_eq!((color.red, color.green, color.blue, color.alpha), (1.0, 0.0, 0.0, 1.0));
+        assert_eq!(spans[1].get_style().get_size(), Some(24));
+    }
+
+    #[test]
+    fn double_brackets_are_escaped(){
+        let (text, spans) = parse_markup("[[b]not bold");
+        assert_eq!(text, "[b]not bold");
+        assert!(spans.is_empty());
+    }
+
+    #[test]
+    fn unclosed_tags_run_to_the_end(){
+        let (text, spans) = parse_markup("a[s]bc");
+        assert_eq!(text, "abc");
+        assert_eq!(ranges(&spans), vec![(1, 3)]);
+    }
+
+    #[test]
+    fn anything_else_is_text(){
+        for markup in ["[x]y", "a[/b]", "[size=0]z", "[color=red]w", "open [b"]{
+            let (text, spans) = parse_markup(markup);
+            assert_eq!(text, markup);
+            assert!(spans.is_empty());
+        }
+    }
+}
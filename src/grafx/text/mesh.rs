@@ -0,0 +1,365 @@
+use crate::grafx::Disposable;
+use crate::grafx::materials::Material;
+use crate::grafx::physics::{ Camera, Transformation3D };
+use crate::grafx::text::atlas::GlyphAtlas;
+use crate::grafx::text::font::{ Font, FontManager };
+use crate::grafx::text::layout::{ HorizontalAlign, VerticalAlign, TextLayout };
+use gl::types::{ GLfloat, GLsizei };
+use std::ffi::c_void;
+use std::{ mem, ptr };
+use freetype::face::LoadFlag;
+use lyon_tessellation::{ BuffersBuilder, FillOptions, FillRule, FillTessellator, FillVertex, VertexBuffers };
+use lyon_tessellation::math::{ point, Point };
+use lyon_tessellation::path::{ BuilderImpl, Path, PathEvent };
+use lyon_tessellation::path::builder::{ Flattened, NoAttributes };
+
+// x, y, z and the normal
+const MESH_VERTEX_SIZE:usize = 6;
+
+// Curves are replaced by lines that stray at most this far from them, in pixels of the font size, so
+// larger font sizes give smoother meshes
+const OUTLINE_TOLERANCE:f32 = 0.05;
+
+// Side walls are shaded smooth across corners flatter than this (the cosine of 35 degrees), so curves
+// look round while the corners of letters like "E" stay sharp
+const SMOOTH_CORNER:f32 = 0.82;
+
+type OutlineBuilder = NoAttributes<Flattened<BuilderImpl>>;
+
+// Text turned into a solid: the glyph outlines are filled to make the front and back caps and joined by
+// side walls, depth pixels apart. It is laid out like a Text (in pixels, y up, around its origin, with
+// spans choosing fonts, sizes and styles) and drawn in 3D with a material and a camera. Span colors and
+// underlines are not part of the mesh, the material colors the whole text.
+pub struct TextMesh{
+    voa:u32, vbo:u32, ebo:u32, layout:TextLayout, depth:f32, vertices:Vec<f32>, indices:Vec<u32>, transform:Box<Transformation3D>
+}
+
+#[allow(dead_code)]
+impl TextMesh{
+    pub fn new(text:&str, depth:f32)->Self{
+        TextMesh::with_font(text, FontManager::with(|manager| manager.default_font()), depth)
+    }
+
+    pub fn with_font(text:&str, font:Font, depth:f32)->Self{
+        let (voa, vbo, ebo) = unsafe{
+            let mut voa = 0;
+            let mut vbo = 0;
+            let mut ebo = 0;
+            gl::GenVertexArrays(1, &mut voa);
+            gl::GenBuffers(1, &mut vbo);
+            gl::GenBuffers(1, &mut ebo);
+            gl::BindVertexArray(voa);
+
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
+            let stride = (MESH_VERTEX_SIZE * mem::size_of::<GLfloat>()) as GLsizei;
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, stride, ptr::null());
+            gl::EnableVertexAttribArray(1);
+            gl::VertexAttribPointer(1, 3, gl::FLOAT, gl::FALSE, stride, (3 * mem::size_of::<GLfloat>()) as *const c_void);
+            gl::BindVertexArray(0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+            (voa, vbo, ebo)
+        };
+
+        let mut init = TextMesh{
+            voa, vbo, ebo, layout:TextLayout::new(text, font), depth, vertices:Vec::new(), indices:Vec::new(),
+            transform:Box::new(Transformation3D::new())
+        };
+        init.build_mesh();
+        init
+    }
+
+    // The outline of a glyph, with its curves already flattened, moved to its place in the text. Glyphs
+    // of bitmap-only fonts (color emoji) have no outline.
+    fn get_outline(font:Font, size:u32, glyph:u32, origin:(f32, f32))->Option<Path>{
+        // outline points are in 26.6 fixed point pixels
+        let (points, tags, contours) = FontManager::with(|manager| {
+            manager.set_size(font, size)?;
+            GlyphAtlas::load_outline(manager, font, glyph, LoadFlag::NO_BITMAP | LoadFlag::NO_HINTING).ok()?;
+            let outline = manager.get_face(font).glyph().outline()?;
+            let points:Vec<Point> = outline.points().iter().map(|p| point(origin.0 + p.x as f32 / 64.0, origin.1 + p.y as f32 / 64.0)).collect();
+            Some((points, outline.tags().to_vec(), outline.contours().to_vec()))
+        })?;
+
+        let mut builder = Path::builder().flattened(OUTLINE_TOLERANCE);
+        let mut first = 0;
+        for last in contours.iter().map(|last| *last as usize){
+            if last < first || last >= points.len() {
+                break;
+            }
+            TextMesh::add_contour(&mut builder, &points[first..=last], &tags[first..=last]);
+            first = last + 1;
+        }
+        Some(builder.build())
+    }
+
+    // Adds one closed contour of a FreeType outline. Besides the points on the curve there are control
+    // points of quadratic (conic) and cubic curves; two conic controls in a row have an implied point on
+    // the curve halfway between them.
+    fn add_contour(builder:&mut OutlineBuilder, points:&[Point], tags:&[i8]){
+        const ON:i8 = 1;
+        const CUBIC:i8 = 2;
+        let count = points.len();
+        let mut expanded = Vec::with_capacity(count * 2);
+        for i in 0..count{
+            let kind = tags[i] & 3;
+            expanded.push((points[i], kind));
+            let next = (i + 1) % count;
+            if kind == 0 && tags[next] & 3 == 0 {
+                expanded.push((points[i].lerp(points[next], 0.5), ON));
+            }
+        }
+        // a contour may begin with a control point, it is walked from its first point on the curve
+        let start = match expanded.iter().position(|(_, kind)| *kind == ON) {
+            Some(start) => start,
+            None => return,
+        };
+        expanded.rotate_left(start);
+
+        let count = expanded.len();
+        let at = |i:usize| expanded[i % count].0;
+        builder.begin(at(0));
+        let mut i = 1;
+        while i <= count {
+            match expanded[i % count].1 {
+                ON => {
+                    // the contour is closed back to its start by end()
+                    if i < count { builder.line_to(at(i)); }
+                    i += 1;
+                },
+                CUBIC => {
+                    builder.cubic_bezier_to(at(i), at(i + 1), at(i + 2));
+                    i += 3;
+                },
+                _ => {
+                    builder.quadratic_bezier_to(at(i), at(i + 1));
+                    i += 2;
+                },
+            }
+        }
+        builder.end(true);
+    }
+
+    fn build_mesh(&mut self){
+        self.vertices.clear();
+        self.indices.clear();
+
+        let mut tessellator = FillTessellator::new();
+        for (font, size, glyph, origin) in self.layout.get_glyph_origins(){
+            if let Some(path) = TextMesh::get_outline(font, size, glyph, origin) {
+                self.add_caps(&mut tessellator, &path);
+                self.add_walls(&path);
+            }
+        }
+
+        unsafe{
+            gl::BindVertexArray(self.voa);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            gl::BufferData(gl::ARRAY_BUFFER, (self.vertices.len() * mem::size_of::<GLfloat>()) as isize, self.vertices.as_ptr() as *const c_void, gl::STATIC_DRAW);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
+            gl::BufferData(gl::ELEMENT_ARRAY_BUFFER, (self.indices.len() * mem::size_of::<u32>()) as isize, self.indices.as_ptr() as *const c_void, gl::STATIC_DRAW);
+            gl::BindVertexArray(0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+    }
+
+    fn add_vertex(&mut self, position:[f32; 3], normal:[f32; 3])->u32{
+        let index = (self.vertices.len() / MESH_VERTEX_SIZE) as u32;
+        self.vertices.extend_from_slice(&position);
+        self.vertices.extend_from_slice(&normal);
+        index
+    }
+
+    // Adds a triangle wound counter-clockwise when seen from the side its normal points to
+    fn add_triangle(&mut self, a:u32, b:u32, c:u32, normal:[f32; 3]){
+        let position = |index:u32| {
+            let offset = index as usize * MESH_VERTEX_SIZE;
+            [self.vertices[offset], self.vertices[offset + 1], self.vertices[offset + 2]]
+        };
+        let (pa, pb, pc) = (position(a), position(b), position(c));
+        let (u, v) = ([pb[0] - pa[0], pb[1] - pa[1], pb[2] - pa[2]], [pc[0] - pa[0], pc[1] - pa[1], pc[2] - pa[2]]);
+        let cross = [u[1] * v[2] - u[2] * v[1], u[2] * v[0] - u[0] * v[2], u[0] * v[1] - u[1] * v[0]];
+        let facing = cross[0] * normal[0] + cross[1] * normal[1] + cross[2] * normal[2];
+        if facing >= 0.0 {
+            self.indices.extend_from_slice(&[a, b, c]);
+        }else{
+            self.indices.extend_from_slice(&[a, c, b]);
+        }
+    }
+
+    // The filled outline twice: facing the viewer at half the depth in front, and away at half behind
+    fn add_caps(&mut self, tessellator:&mut FillTessellator, path:&Path){
+        let mut buffers:VertexBuffers<Point, u32> = VertexBuffers::new();
+        let options = FillOptions::tolerance(OUTLINE_TOLERANCE).with_fill_rule(FillRule::NonZero);
+        if tessellator.tessellate_path(path, &options, &mut BuffersBuilder::new(&mut buffers, |vertex:FillVertex| vertex.position())).is_err() {
+            return;
+        }
+
+        let half = self.depth / 2.0;
+        for (z, normal) in [(half, [0.0, 0.0, 1.0]), (-half, [0.0, 0.0, -1.0])]{
+            let base = (self.vertices.len() / MESH_VERTEX_SIZE) as u32;
+            for position in &buffers.vertices{
+                self.add_vertex([position.x, position.y, z], normal);
+            }
+            for triangle in buffers.indices.chunks(3){
+                self.add_triangle(base + triangle[0], base + triangle[1], base + triangle[2], normal);
+            }
+        }
+    }
+
+    // One quad from the front cap to the back cap along every edge of the outline
+    fn add_walls(&mut self, path:&Path){
+        let mut contours:Vec<Vec<Point>> = Vec::new();
+        for event in path{
+            match event {
+                PathEvent::Begin{ at } => contours.push(vec![at]),
+                PathEvent::Line{ to, .. } => if let Some(contour) = contours.last_mut() { contour.push(to); },
+                _ => {},
+            }
+        }
+        for contour in &mut contours{
+            contour.dedup_by(|a, b| (*a - *b).square_length() < 1e-8);
+            while contour.len() > 1 && (contour[0] - contour[contour.len() - 1]).square_length() < 1e-8 {
+                contour.pop();
+            }
+        }
+        contours.retain(|contour| contour.len() > 2);
+
+        // the outer contours of TrueType fonts run clockwise and of CFF fonts counter-clockwise; they are
+        // larger than the holes inside them, so the sign of the total area tells which side is filled
+        let area:f32 = contours.iter().map(|contour| {
+            (0..contour.len()).map(|i| {
+                let (a, b) = (contour[i], contour[(i + 1) % contour.len()]);
+                a.x * b.y - b.x * a.y
+            }).sum::<f32>()
+        }).sum();
+        let clockwise = area < 0.0;
+
+        let half = self.depth / 2.0;
+        for contour in &contours{
+            let count = contour.len();
+            let normals:Vec<(f32, f32)> = (0..count).map(|i| {
+                let direction = (contour[(i + 1) % count] - contour[i]).normalize();
+                // outwards is left of the direction for clockwise outlines and right for the others
+                if clockwise { (-direction.y, direction.x) } else { (direction.y, -direction.x) }
+            }).collect();
+            // the normal at the point an edge starts from, shared with the edge before it when smooth
+            let corner = |i:usize, edge:(f32, f32)| {
+                let before = normals[(i + count - 1) % count];
+                let after = normals[i];
+                if before.0 * after.0 + before.1 * after.1 < SMOOTH_CORNER {
+                    return edge;
+                }
+                let (x, y) = (before.0 + after.0, before.1 + after.1);
+                let length = (x * x + y * y).sqrt().max(1e-6);
+                (x / length, y / length)
+            };
+
+            for i in 0..count{
+                let next = (i + 1) % count;
+                let (start, end) = (corner(i, normals[i]), corner(next, normals[i]));
+                let (from, to) = (contour[i], contour[next]);
+                let a = self.add_vertex([from.x, from.y, half], [start.0, start.1, 0.0]);
+                let b = self.add_vertex([to.x, to.y, half], [end.0, end.1, 0.0]);
+                let c = self.add_vertex([to.x, to.y, -half], [end.0, end.1, 0.0]);
+                let d = self.add_vertex([from.x, from.y, -half], [start.0, start.1, 0.0]);
+                let normal = [normals[i].0, normals[i].1, 0.0];
+                self.add_triangle(a, b, c, normal);
+                self.add_triangle(a, c, d, normal);
+            }
+        }
+    }
+
+    pub fn set_text(&mut self, data:&str){
+        self.layout.set_text(data);
+        self.build_mesh();
+    }
+
+    pub fn set_markup(&mut self, markup:&str){
+        self.layout.set_markup(markup);
+        self.build_mesh();
+    }
+
+    // The font size sets how detailed the mesh is as well as how large, see OUTLINE_TOLERANCE
+    pub fn set_font_size(&mut self, size:u32){
+        self.layout.set_font_size(size);
+        self.build_mesh();
+    }
+
+    pub fn set_font(&mut self, font:Font){
+        self.layout.set_font(font);
+        self.build_mesh();
+    }
+
+    pub fn set_alignment(&mut self, horizontal:HorizontalAlign, vertical:VerticalAlign){
+        self.layout.set_alignment(horizontal, vertical);
+        self.build_mesh();
+    }
+
+    pub fn set_line_spacing(&mut self, spacing:f32){
+        self.layout.set_line_spacing(spacing);
+        self.build_mesh();
+    }
+
+    pub fn set_max_width(&mut self, width:Option<f32>){
+        self.layout.set_max_width(width);
+        self.build_mesh();
+    }
+
+    pub fn set_variation(&mut self, tag:&str, value:f32){
+        self.layout.set_variation(tag, value);
+        self.build_mesh();
+    }
+
+    // Distance between the front and back caps, in pixels of the font size
+    pub fn set_depth(&mut self, depth:f32){
+        self.depth = depth;
+        self.build_mesh();
+    }
+
+    pub fn get_layout(&self)->&TextLayout{ &self.layout }
+    pub fn get_text(&self)->&str{ self.layout.get_text() }
+    pub fn get_font(&self)->Font{ self.layout.get_font() }
+    pub fn get_font_size(&self)->u32{ self.layout.get_font_size() }
+    pub fn get_depth(&self)->f32{ self.depth }
+    pub fn get_width(&self)->f32{ self.layout.get_width() }
+    pub fn get_height(&self)->f32{ self.layout.get_height() }
+    pub fn get_triangle_count(&self)->usize{ self.indices.len() / 3 }
+
+    // Place, rotation and scale of the text in the world; call update() on it after changing them
+    pub fn get_transform(&mut self)->&mut Transformation3D{
+        &mut self.transform
+    }
+
+    // Draws the mesh lit from the camera's position. The material's shader takes the model, view and
+    // projection matrices and the light and camera positions (see shaders/simple.vs and simple.fs).
+    pub fn draw(&self, material:&dyn Material, camera:&Camera){
+        unsafe{
+            material.r#use();
+            let shader = material.get_shader();
+            shader.set_uniform_matrix4("model", self.transform.getTransformMatrix());
+            shader.set_uniform_matrix4("view", camera.get_view());
+            shader.set_uniform_matrix4("projection", camera.get_projection());
+            shader.set_uniform_vector3("lightPosition", camera.get_position());
+            shader.set_uniform_vector3("viewPosition", camera.get_position());
+
+            gl::BindVertexArray(self.voa);
+            gl::DrawElements(gl::TRIANGLES, self.indices.len() as GLsizei, gl::UNSIGNED_INT, ptr::null());
+            gl::BindVertexArray(0);
+        }
+    }
+}
+
+impl Disposable for TextMesh{
+    fn dispose(&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+        unsafe{
+            gl::DeleteBuffers(1, &self.vbo);
+            gl::DeleteBuffers(1, &self.ebo);
+            gl::DeleteVertexArrays(1, &self.voa);
+        }
+    }
+}
@@ -0,0 +1,435 @@
+use crate::Disposable;
+use crate::grafx::physics::Rectangle;
+use crate::grafx::physics::Transformation2D;
+use crate::grafx::physics::Viewport;
+use gl::types::GLsizei;
+use gl::types::GLfloat;
+use crate::grafx::materials::shader::Shader;
+use crate::grafx::physics::Color;
+use crate::grafx::physics::Vector2;
+use std::ffi::c_void;
+use std::{ mem, ptr};
+
+pub mod atlas;
+use atlas::{ GlyphAtlas, DISTANCE_FIELD_SPREAD };
+pub use atlas::TextRenderMode;
+
+mod batch;
+pub use batch::TextBatch;
+
+pub mod font;
+use font::{ Font, FontManager, FontVariation };
+
+pub mod shaping;
+use shaping::FontFeature;
+
+pub mod style;
+pub use style::TextStyle;
+use style::StyleLayer;
+
+pub mod fill;
+pub use fill::TextFill;
+
+pub mod span;
+pub use span::{ SpanStyle, TextSpan };
+
+pub mod markup;
+
+pub mod layout;
+pub use layout::{ HorizontalAlign, VerticalAlign, TextDirection, TextLayout, TextMetrics };
+
+mod input;
+pub use input::TextInput;
+
+mod mesh;
+pub use mesh::TextMesh;
+
+mod world;
+pub use world::Text3D;
+
+// x, y, u, v, the position inside the text's bounding box that fills are laid out on, and the color
+pub(crate) const QUAD_VERTEX_SIZE:usize = 10;
+
+thread_local!{
+    static TEXT_SHADER:Shader = unsafe{ Shader::text() };
+}
+
+// Runs f with the text shader, which is shared by Text, TextBatch and TextInput and only compiled once,
+// the first time the thread that owns the GL context draws text
+fn with_text_shader<R>(f:impl FnOnce(&Shader)->R)->R{
+    TEXT_SHADER.with(f)
+}
+
+// A slice of the element buffer whose glyphs all sample the same atlas page
+pub(crate) struct DrawRange{ page:usize, offset:usize, count:usize }
+
+// A string drawn with OpenGL. The layout (shaping, wrapping, alignment) lives in a TextLayout; the Text
+// turns its quads into a mesh and keeps what only matters for drawing: colors, fill, style and transform.
+// The vertices and indices are kept as they were last uploaded, so a change only sends what differs.
+pub struct Text{
+    voa:u32, vbo:u32, ebo:u32, layout:TextLayout, render_mode:TextRenderMode, vertices:Vec<f32>, indices:Vec<u32>, ranges:Vec<DrawRange>,
+    vertex_capacity:usize, index_capacity:usize, color:Box<Color>, fill:TextFill, style:TextStyle, transform:Box<Transformation2D>
+}
+
+pub trait Collidable<T>{
+    fn get_boundary(&self)->T;
+}
+
+#[allow(dead_code)]
+impl Text{
+    pub fn new(text:&str)->Self{
+        Text::with_font(text, FontManager::with(|manager| manager.default_font()))
+    }
+
+    pub fn with_font(text:&str, font:Font)->Self{
+        let (voa, vbo, ebo) = unsafe{
+            let mut voa = 0;
+            let mut vbo = 0;
+            let mut ebo = 0;
+            gl::GenVertexArrays(1, &mut voa);
+            gl::GenBuffers(1, &mut vbo);
+            gl::GenBuffers(1, &mut ebo);
+            gl::BindVertexArray(voa);
+            with_text_shader(|shader| shader.bind());
+
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
+            let stride = (QUAD_VERTEX_SIZE * mem::size_of::<GLfloat>()) as GLsizei;
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(0, 4, gl::FLOAT, gl::FALSE, stride, ptr::null());
+            gl::EnableVertexAttribArray(3);
+            gl::VertexAttribPointer(3, 2, gl::FLOAT, gl::FALSE, stride, (4 * mem::size_of::<GLfloat>()) as *const c_void);
+            gl::VertexAttribPointer(1, 4, gl::FLOAT, gl::FALSE, stride, (6 * mem::size_of::<GLfloat>()) as *const c_void);
+            gl::BindVertexArray(0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+            (voa, vbo, ebo)
+        };
+
+        let mut init = Text{
+            voa, vbo, ebo, layout:TextLayout::new(text, font), render_mode:TextRenderMode::Bitmap,
+            vertices:Vec::new(), indices:Vec::new(), ranges:Vec::new(), vertex_capacity:0, index_capacity:0,
+            color:Box::new(Color::White()), fill:TextFill::Solid, style:TextStyle::new(), transform:Box::new(Transformation2D::new())
+        };
+        init.build_mesh();
+        init
+    }
+
+    // Outlines, glows and shadows are drawn from distance fields, so a styled text always uses them
+    fn get_glyph_mode(&self)->TextRenderMode{
+        if self.style.has_effects() { TextRenderMode::DistanceField } else { self.render_mode }
+    }
+
+    // Turns the quads of the layout into 4 vertices and 6 indices each and uploads what changed.
+    // The quads are sorted by atlas page, so every page is drawn with a single call.
+    fn build_mesh(&mut self){
+        let mut vertices = Vec::with_capacity(self.layout.get_quads().len() * 4 * QUAD_VERTEX_SIZE);
+        self.ranges.clear();
+
+        // position of a point inside the bounding box, from 0 to 1 on both axes
+        let [box_left, box_bottom, box_right, box_top] = self.layout.get_bounds();
+        let fill_x = |x:f32| (x - box_left) / (box_right - box_left).max(1.0);
+        let fill_y = |y:f32| (y - box_bottom) / (box_top - box_bottom).max(1.0);
+
+        for (i, quad) in self.layout.get_quads().iter().enumerate(){
+            let ((xpos, ypos), (width, height), [left, top, right, bottom]) = (quad.position, quad.size, quad.uv);
+            let (fill_left, fill_right) = (fill_x(xpos), fill_x(xpos + width));
+            let (fill_bottom, fill_top) = (fill_y(ypos), fill_y(ypos + height));
+            let Color{ red, green, blue, alpha } = quad.color.unwrap_or(*self.color);
+            vertices.extend_from_slice(&[
+                xpos,         ypos + height,    left,  top,       fill_left,  fill_top,       red, green, blue, alpha,
+                xpos,         ypos,             left,  bottom,    fill_left,  fill_bottom,    red, green, blue, alpha,
+                xpos + width, ypos,             right, bottom,    fill_right, fill_bottom,    red, green, blue, alpha,
+                xpos + width, ypos + height,    right, top,       fill_right, fill_top,       red, green, blue, alpha
+            ]);
+
+            match self.ranges.last_mut() {
+                Some(range) if range.page == quad.page => range.count += 6,
+                _ => self.ranges.push(DrawRange{ page:quad.page, offset:i * 6, count:6 }),
+            }
+        }
+
+        // the indices of n quads are the first ones of any longer text, so they only ever grow
+        let quads = self.layout.get_quads().len();
+        let indexed = self.indices.len();
+        for base in (indexed / 6..quads).map(|quad| (quad * 4) as u32){
+            self.indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+
+        let previous = mem::replace(&mut self.vertices, vertices);
+        unsafe{ self.upload(&previous, indexed); }
+    }
+
+    // Sends the mesh to the buffers. They are only reallocated when the mesh outgrows them; otherwise only
+    // the vertices from the first one that differs from the previous mesh to the last one are sent, so a
+    // label whose digits change rewrites a few quads, and only the indices added since the last upload.
+    unsafe fn upload(&mut self, previous:&[f32], indexed:usize){
+        let float = mem::size_of::<GLfloat>();
+        gl::BindVertexArray(self.voa);
+        gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+        if self.vertices.len() > self.vertex_capacity {
+            gl::BufferData(gl::ARRAY_BUFFER, (self.vertices.len() * float) as isize, self.vertices.as_ptr() as *const c_void, gl::DYNAMIC_DRAW);
+            self.vertex_capacity = self.vertices.len();
+        }else{
+            let first = self.vertices.iter().zip(previous).position(|(new, old)| new != old).unwrap_or(self.vertices.len().min(previous.len()));
+            let last = if self.vertices.len() > previous.len() {
+                self.vertices.len()
+            }else{
+                self.vertices.iter().zip(previous).rposition(|(new, old)| new != old).map_or(first, |last| last + 1)
+            };
+            if last > first {
+                gl::BufferSubData(gl::ARRAY_BUFFER, (first * float) as isize, ((last - first) * float) as isize, self.vertices[first..].as_ptr() as *const c_void);
+            }
+        }
+
+        gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
+        let index = mem::size_of::<u32>();
+        if self.indices.len() > self.index_capacity {
+            gl::BufferData(gl::ELEMENT_ARRAY_BUFFER, (self.indices.len() * index) as isize, self.indices.as_ptr() as *const c_void, gl::DYNAMIC_DRAW);
+            self.index_capacity = self.indices.len();
+        }else if self.indices.len() > indexed {
+            gl::BufferSubData(gl::ELEMENT_ARRAY_BUFFER, (indexed * index) as isize, ((self.indices.len() - indexed) * index) as isize, self.indices[indexed..].as_ptr() as *const c_void);
+        }
+        gl::BindVertexArray(0);
+        gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+    }
+
+    pub fn set_text(&mut self, data:&str){
+        if self.layout.get_text() == data && self.layout.get_spans().is_empty() {
+            return;
+        }
+        self.layout.set_text(data);
+        self.build_mesh();
+    }
+
+    pub fn set_markup(&mut self, markup:&str){
+        self.layout.set_markup(markup);
+        self.build_mesh();
+    }
+
+    pub fn set_spans(&mut self, spans:Vec<TextSpan>){
+        self.layout.set_spans(spans);
+        self.build_mesh();
+    }
+
+    pub fn add_span(&mut self, start:usize, end:usize, style:SpanStyle){
+        self.layout.add_span(start, end, style);
+        self.build_mesh();
+    }
+
+    pub fn set_font_size(&mut self, size:u32){
+        if self.layout.get_font_size() != size {
+            self.layout.set_font_size(size);
+            self.build_mesh();
+        }
+    }
+
+    pub fn set_render_mode(&mut self, mode:TextRenderMode){
+        self.render_mode = mode;
+        self.layout.set_render_mode(self.get_glyph_mode());
+        self.build_mesh();
+    }
+
+    pub fn set_font(&mut self, font:Font){
+        self.layout.set_font(font);
+        self.build_mesh();
+    }
+
+    pub fn set_direction(&mut self, direction:TextDirection){
+        self.layout.set_direction(direction);
+        self.build_mesh();
+    }
+
+    pub fn set_alignment(&mut self, horizontal:HorizontalAlign, vertical:VerticalAlign){
+        self.layout.set_alignment(horizontal, vertical);
+        self.build_mesh();
+    }
+
+    pub fn set_line_spacing(&mut self, spacing:f32){
+        self.layout.set_line_spacing(spacing);
+        self.build_mesh();
+    }
+
+    pub fn set_max_width(&mut self, width:Option<f32>){
+        self.layout.set_max_width(width);
+        self.build_mesh();
+    }
+
+    pub fn set_kerning(&mut self, enabled:bool){
+        self.layout.set_kerning(enabled);
+        self.build_mesh();
+    }
+
+    pub fn set_feature(&mut self, tag:&str, enabled:bool){
+        self.layout.set_feature(tag, enabled);
+        self.build_mesh();
+    }
+
+    pub fn set_variation(&mut self, tag:&str, value:f32){
+        self.layout.set_variation(tag, value);
+        self.build_mesh();
+    }
+
+    // Colors every glyph that is not in a colored span. Nothing moves, so only the colors of the
+    // vertices are rewritten and the layout is left as it is.
+    pub fn set_color(&mut self, red:f32, green:f32, blue:f32, alpha:f32){
+        self.color = Box::new(Color{red, green, blue, alpha});
+        let previous = self.vertices.clone();
+        for (quad, vertices) in self.layout.get_quads().iter().zip(self.vertices.chunks_mut(4 * QUAD_VERTEX_SIZE)){
+            if quad.color.is_none() {
+                for vertex in vertices.chunks_mut(QUAD_VERTEX_SIZE){
+                    vertex[6..10].copy_from_slice(&[red, green, blue, alpha]);
+                }
+            }
+        }
+        let indexed = self.indices.len();
+        unsafe{ self.upload(&previous, indexed); }
+    }
+
+    // Paints the glyphs with a gradient or texture instead of the plain text color
+    pub fn set_fill(&mut self, fill:TextFill){
+        self.fill = fill;
+    }
+
+    pub fn set_style(&mut self, style:TextStyle){
+        self.style = style;
+        // the glyphs only change when the effects switch the text to (or back from) distance fields
+        if self.layout.get_render_mode() != self.get_glyph_mode() {
+            self.layout.set_render_mode(self.get_glyph_mode());
+            self.build_mesh();
+        }
+    }
+
+    pub fn get_layout(&self)->&TextLayout{ &self.layout }
+    pub fn get_text(&self)->&str{ self.layout.get_text() }
+    pub fn get_spans(&self)->&[TextSpan]{ self.layout.get_spans() }
+    pub fn get_font(&self)->Font{ self.layout.get_font() }
+    pub fn get_font_size(&self)->u32{ self.layout.get_font_size() }
+    pub fn get_render_mode(&self)->TextRenderMode{ self.render_mode }
+    pub fn get_direction(&self)->TextDirection{ self.layout.get_direction() }
+    pub fn get_horizontal_align(&self)->HorizontalAlign{ self.layout.get_horizontal_align() }
+    pub fn get_vertical_align(&self)->VerticalAlign{ self.layout.get_vertical_align() }
+    pub fn get_line_spacing(&self)->f32{ self.layout.get_line_spacing() }
+    pub fn get_max_width(&self)->Option<f32>{ self.layout.get_max_width() }
+    pub fn is_kerning(&self)->bool{ self.layout.is_kerning() }
+    pub fn get_features(&self)->&[FontFeature]{ self.layout.get_features() }
+    pub fn get_variations(&self)->&[FontVariation]{ self.layout.get_variations() }
+    pub fn get_metrics(&self)->&TextMetrics{ self.layout.get_metrics() }
+    pub fn get_width(&self)->f32{ self.layout.get_width() }
+    pub fn get_height(&self)->f32{ self.layout.get_height() }
+
+    // The character under a point in world space, as its byte index in the text and whether the point is
+    // on its leading edge. The point is taken back through the transform, so rotated and scaled text works.
+    pub fn hit_test(&self, point:&Vector2)->Option<(usize, bool)>{
+        let matrix = self.transform.getTransformMatrix();
+        let determinant = matrix[0][0] * matrix[1][1] - matrix[0][1] * matrix[1][0];
+        if determinant == 0.0 {
+            return None;
+        }
+        let (x, y) = (point.getX() - matrix[0][2], point.getY() - matrix[1][2]);
+        let local_x = (matrix[1][1] * x - matrix[0][1] * y) / determinant;
+        let local_y = (matrix[0][0] * y - matrix[1][0] * x) / determinant;
+        self.layout.hit_test(local_x, local_y)
+    }
+
+    // Where a caret before the character at a byte index is drawn, on its line's baseline in world space
+    pub fn caret_position(&self, index:usize)->Vector2{
+        let matrix = self.transform.getTransformMatrix();
+        let (x, y) = self.layout.caret_position(index);
+        Vector2::new(matrix[0][0] * x + matrix[0][1] * y + matrix[0][2], matrix[1][0] * x + matrix[1][1] * y + matrix[1][2])
+    }
+
+    pub fn get_transform(&mut self)->&mut Transformation2D{
+        &mut self.transform
+    }
+
+    pub fn get_color(&self)->&Color{ &self.color }
+    pub fn get_fill(&self)->&TextFill{ &self.fill }
+    pub fn get_style(&self)->&TextStyle{ &self.style }
+
+    // The passes that draw this text, from the shadow up to the glyphs themselves
+    pub(crate) fn get_layers(&self)->Vec<StyleLayer>{
+        // one distance field unit spans twice the spread, in pixels of the rasterized glyph. Effects are
+        // measured at the text's font size, so they grow with the glyphs of spans in larger sizes.
+        let units = 1.0 / (2.0 * DISTANCE_FIELD_SPREAD as f32 * self.layout.get_glyph_scale(self.layout.get_font_size()));
+        self.style.get_layers(&self.color, units)
+    }
+    pub(crate) fn get_vertices(&self)->&[f32]{ &self.vertices }
+    pub(crate) fn get_ranges(&self)->&[DrawRange]{ &self.ranges }
+            
+    pub fn draw(&self, port:&Viewport){
+        unsafe{
+            with_text_shader(|shader| {
+                shader.bind();
+                shader.set_uniform_matrix4("projection", port.get_data());
+                shader.set_uniform_matrix3("transform", self.transform.getTransformMatrix());
+                self.draw_layers(shader);
+            });
+        }
+    }
+
+    // Draws every layer with a shader whose transform uniforms are already set
+    pub(crate) unsafe fn draw_layers(&self, shader:&Shader){
+        gl::BindVertexArray(self.voa);
+        gl::ActiveTexture(gl::TEXTURE0);
+        shader.set_uniform_color("textColor", &Color::White());
+        // the layers of a text overlap, so they must not hide each other through the depth buffer
+        gl::DepthMask(gl::FALSE);
+
+        for layer in self.get_layers(){
+            // the glyphs take their colors from the mesh, the effects are one color held constant
+            let color = &layer.color;
+            if layer.filled {
+                gl::EnableVertexAttribArray(1);
+            }else{
+                gl::DisableVertexAttribArray(1);
+                gl::VertexAttrib4f(1, color.red, color.green, color.blue, color.alpha);
+            }
+            gl::VertexAttrib3f(2, layer.edge, layer.softness, layer.filled as i32 as f32);
+            shader.set_uniform_vector2("offset", layer.offset.0, layer.offset.1);
+            if layer.filled { self.fill.apply(shader); } else { TextFill::Solid.apply(shader); }
+
+            for range in &self.ranges{
+                GlyphAtlas::with(|atlas| {
+                    shader.set_uniform_int("distanceField", (atlas.get_page_mode(range.page) == TextRenderMode::DistanceField) as i32);
+                    shader.set_uniform_int("colorGlyphs", atlas.is_color_page(range.page) as i32);
+                    atlas.bind_page(range.page);
+                });
+                gl::DrawElements(gl::TRIANGLES, range.count as GLsizei, gl::UNSIGNED_INT, (range.offset * mem::size_of::<u32>()) as *const c_void);
+            }
+        }
+        gl::DepthMask(gl::TRUE);
+        gl::BindVertexArray(0);
+        gl::BindTexture(gl::TEXTURE_2D, 0);
+    }
+}
+
+impl Collidable<Rectangle> for Text{
+    fn get_boundary(&self) -> Rectangle {
+        // the ink is not centered on the position, so its center is moved through the transform
+        let matrix = self.transform.getTransformMatrix();
+        let metrics = self.get_metrics();
+        let [left, bottom, right, top] = metrics.ink_bounds;
+        let center_x = (left + right) / 2.0;
+        let center_y = (bottom + top) / 2.0;
+        let scale = self.transform.get_scale();
+        Rectangle::new(
+            matrix[0][0] * center_x + matrix[0][1] * center_y + matrix[0][2],
+            matrix[1][0] * center_x + matrix[1][1] * center_y + matrix[1][2],
+            metrics.get_ink_width() * scale.getX(), metrics.get_ink_height() * scale.getY(), self.transform.get_rotation())
+    }
+}
+
+impl Disposable for Text{
+    fn dispose(&mut self) {
+        // glyph bitmaps live in the shared atlas, only the buffers belong to this text
+        self.vertices.clear();
+        self.indices.clear();
+        self.ranges.clear();
+        unsafe{
+            gl::DeleteBuffers(1, &self.vbo);
+            gl::DeleteBuffers(1, &self.ebo);
+            gl::DeleteVertexArrays(1, &self.voa);
+        }
+    }
+}
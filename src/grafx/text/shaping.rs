@@ -0,0 +1,59 @@
+use crate::grafx::text::font::{ Font, FontManager };
+use rustybuzz::{ Direction, Feature, UnicodeBuffer, Variation };
+use rustybuzz::ttf_parser::Tag;
+
+// A glyph positioned by the shaper, in pixels. The cluster is the byte offset in the text of the
+// first character the glyph was made from.
+#[derive(Clone, Copy, Debug)]
+pub struct ShapedGlyph{ pub glyph:u32, pub cluster:usize, pub x_advance:f32, pub x_offset:f32, pub y_offset:f32 }
+
+// An OpenType feature such as liga, kern, smcp or tnum, turned on or off for a whole text
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FontFeature{ tag:[u8; 4], enabled:bool }
+
+#[allow(dead_code)]
+impl FontFeature{
+    pub fn new(tag:&str, enabled:bool)->Self{
+        let mut bytes = [b' '; 4];
+        for (byte, value) in bytes.iter_mut().zip(tag.bytes()){
+            *byte = value;
+        }
+        FontFeature{ tag:bytes, enabled }
+    }
+
+    pub fn get_tag(&self)->&str{ std::str::from_utf8(&self.tag).unwrap_or("") }
+    pub fn is_enabled(&self)->bool{ self.enabled }
+}
+
+// Shapes a run of text that has a single direction with the font's OpenType tables. Glyphs come back in
+// logical order, so a right-to-left run has to be reversed again when it is placed on a line.
+// None is returned when the font can not be read by the shaper.
+pub fn shape(font:Font, size:u32, text:&str, offset:usize, rtl:bool, features:&[FontFeature])->Option<Vec<ShapedGlyph>>{
+    let (data, variations) = FontManager::with(|manager| (manager.get_shared_data(font), manager.get_variations(font).to_vec()));
+    let mut face = rustybuzz::Face::from_slice(&data, 0)?;
+    let variations:Vec<Variation> = variations.iter()
+        .map(|variation| Variation{ tag:Tag::from_bytes(variation.get_tag_bytes()), value:variation.get_value() })
+        .collect();
+    face.set_variations(&variations);
+    let scale = size as f32 / face.units_per_em() as f32;
+
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str(text);
+    buffer.guess_segment_properties();
+    buffer.set_direction(if rtl { Direction::RightToLeft } else { Direction::LeftToRight });
+
+    let features:Vec<Feature> = features.iter()
+        .map(|feature| Feature::new(Tag::from_bytes(&feature.tag), feature.enabled as u32, ..))
+        .collect();
+    let output = rustybuzz::shape(&face, &features, buffer);
+
+    let mut glyphs:Vec<ShapedGlyph> = output.glyph_infos().iter().zip(output.glyph_positions()).map(|(info, position)| ShapedGlyph{
+        glyph:info.glyph_id, cluster:offset + info.cluster as usize,
+        x_advance:position.x_advance as f32 * scale,
+        x_offset:position.x_offset as f32 * scale, y_offset:position.y_offset as f32 * scale,
+    }).collect();
+    if rtl {
+        glyphs.reverse();
+    }
+    Some(glyphs)
+}
@@ -0,0 +1,59 @@
+use crate::grafx::physics::Color;
+use crate::grafx::text::font::Font;
+
+// Attributes a span overrides on the part of a Text it covers; anything left unset is taken from the
+// Text itself (or from an enclosing span).
+#[derive(Clone, Copy, Default)]
+pub struct SpanStyle{
+    color:Option<Color>, size:Option<u32>, font:Option<Font>,
+    bold:Option<bool>, italic:Option<bool>, underline:Option<bool>, strikethrough:Option<bool>
+}
+
+#[allow(dead_code)]
+impl SpanStyle{
+    pub fn new()->Self{ SpanStyle::default() }
+
+    pub fn set_color(&mut self, color:Color){ self.color = Some(color); }
+    pub fn set_size(&mut self, size:u32){ self.size = Some(size); }
+    pub fn set_font(&mut self, font:Font){ self.font = Some(font); }
+    // Bold and italic pick the variants registered with FontManager::set_variant
+    pub fn set_bold(&mut self, bold:bool){ self.bold = Some(bold); }
+    pub fn set_italic(&mut self, italic:bool){ self.italic = Some(italic); }
+    pub fn set_underline(&mut self, underline:bool){ self.underline = Some(underline); }
+    pub fn set_strikethrough(&mut self, strikethrough:bool){ self.strikethrough = Some(strikethrough); }
+
+    pub fn get_color(&self)->Option<&Color>{ self.color.as_ref() }
+    pub fn get_size(&self)->Option<u32>{ self.size }
+    pub fn get_font(&self)->Option<Font>{ self.font }
+    pub fn is_bold(&self)->bool{ self.bold.unwrap_or(false) }
+    pub fn is_italic(&self)->bool{ self.italic.unwrap_or(false) }
+    pub fn is_underline(&self)->bool{ self.underline.unwrap_or(false) }
+    pub fn is_strikethrough(&self)->bool{ self.strikethrough.unwrap_or(false) }
+
+    // This style with everything the other one sets laid over it
+    pub(crate) fn merge(&self, other:&SpanStyle)->SpanStyle{
+        SpanStyle{
+            color:other.color.or(self.color), size:other.size.or(self.size), font:other.font.or(self.font),
+            bold:other.bold.or(self.bold), italic:other.italic.or(self.italic),
+            underline:other.underline.or(self.underline), strikethrough:other.strikethrough.or(self.strikethrough)
+        }
+    }
+}
+
+// A style applied to the bytes start..end of a Text. Where spans overlap, the one added last wins.
+#[derive(Clone, Copy)]
+pub struct TextSpan{ start:usize, end:usize, style:SpanStyle }
+
+#[allow(dead_code)]
+impl TextSpan{
+    pub fn new(start:usize, end:usize, style:SpanStyle)->Self{
+        TextSpan{ start, end:end.max(start), style }
+    }
+
+    pub fn get_start(&self)->usize{ self.start }
+    pub fn get_end(&self)->usize{ self.end }
+    pub fn get_style(&self)->&SpanStyle{ &self.style }
+    pub(crate) fn set_end(&mut self, end:usize){ self.end = end.max(self.start); }
+
+    pub fn contains(&self, index:usize)->bool{ index >= self.start && index < self.end }
+}
@@ -0,0 +1,89 @@
+use crate::grafx::physics::Color;
+
+// Effects drawn around the glyphs of a Text: an outline (stroke), an outer glow and a drop shadow.
+// All sizes are in pixels at the text's font size. The effects are drawn from distance field glyphs,
+// which only reach DISTANCE_FIELD_SPREAD pixels (at DISTANCE_FIELD_SIZE) outside the outline, so larger
+// strokes and glows are cut off at that distance.
+#[derive(Clone, Copy)]
+pub struct TextStyle{
+    outline_width:f32, outline_color:Color,
+    glow_size:f32, glow_color:Color,
+    shadow_offset:(f32, f32), shadow_blur:f32, shadow_color:Color
+}
+
+// One pass over the glyphs of a Text. The edge is the distance field value the layer starts at and the
+// softness how far (in distance field units) it fades out on both sides of that edge. Only the glyphs
+// themselves are painted with the text's fill, the effects are solid.
+#[derive(Clone, Copy)]
+pub(crate) struct StyleLayer{ pub color:Color, pub offset:(f32, f32), pub edge:f32, pub softness:f32, pub filled:bool }
+
+#[allow(dead_code)]
+impl TextStyle{
+    pub fn new()->Self{
+        let none = Color::new(0.0, 0.0, 0.0, 0.0);
+        TextStyle{
+            outline_width:0.0, outline_color:none,
+            glow_size:0.0, glow_color:none,
+            shadow_offset:(0.0, 0.0), shadow_blur:0.0, shadow_color:none
+        }
+    }
+
+    pub fn set_outline(&mut self, width:f32, color:Color){
+        self.outline_width = width.max(0.0);
+        self.outline_color = color;
+    }
+
+    pub fn set_glow(&mut self, size:f32, color:Color){
+        self.glow_size = size.max(0.0);
+        self.glow_color = color;
+    }
+
+    // The offset is in screen pixels, so the shadow keeps falling the same way when the text is rotated
+    pub fn set_shadow(&mut self, offset_x:f32, offset_y:f32, blur:f32, color:Color){
+        self.shadow_offset = (offset_x, offset_y);
+        self.shadow_blur = blur.max(0.0);
+        self.shadow_color = color;
+    }
+
+    pub fn get_outline_width(&self)->f32{ self.outline_width }
+    pub fn get_outline_color(&self)->&Color{ &self.outline_color }
+    pub fn get_glow_size(&self)->f32{ self.glow_size }
+    pub fn get_glow_color(&self)->&Color{ &self.glow_color }
+    pub fn get_shadow_offset(&self)->(f32, f32){ self.shadow_offset }
+    pub fn get_shadow_blur(&self)->f32{ self.shadow_blur }
+    pub fn get_shadow_color(&self)->&Color{ &self.shadow_color }
+
+    fn has_outline(&self)->bool{ self.outline_width > 0.0 && self.outline_color.alpha > 0.0 }
+    fn has_glow(&self)->bool{ self.glow_size > 0.0 && self.glow_color.alpha > 0.0 }
+    fn has_shadow(&self)->bool{ self.shadow_color.alpha > 0.0 }
+
+    // Whether anything besides the plain glyphs is drawn
+    pub fn has_effects(&self)->bool{
+        self.has_outline() || self.has_glow() || self.has_shadow()
+    }
+
+    // The passes needed to draw a text in back to front order, ending with the glyphs themselves.
+    // `units` converts a distance in pixels to distance field units for the text's size.
+    pub(crate) fn get_layers(&self, color:&Color, units:f32)->Vec<StyleLayer>{
+        let outline = if self.has_outline() { self.outline_width } else { 0.0 };
+        let edge = |distance:f32| (0.5 - distance * units).max(0.0);
+
+        let mut layers = Vec::new();
+        if self.has_shadow() {
+            layers.push(StyleLayer{ color:self.shadow_color, offset:self.shadow_offset, edge:edge(outline), softness:self.shadow_blur * units, filled:false });
+        }
+        if self.has_glow() {
+            let half = self.glow_size / 2.0;
+            layers.push(StyleLayer{ color:self.glow_color, offset:(0.0, 0.0), edge:edge(outline + half), softness:half * units, filled:false });
+        }
+        if self.has_outline() {
+            layers.push(StyleLayer{ color:self.outline_color, offset:(0.0, 0.0), edge:edge(outline), softness:0.0, filled:false });
+        }
+        layers.push(StyleLayer{ color:*color, offset:(0.0, 0.0), edge:0.5, softness:0.0, filled:true });
+        layers
+    }
+}
+
+impl Default for TextStyle{
+    fn default()->Self{ TextStyle::new() }
+}
@@ -0,0 +1,87 @@
+use crate::grafx::Disposable;
+use crate::grafx::materials::shader::Shader;
+use crate::grafx::physics::{ Camera, Matrix, Matrix4, Transformation3D };
+use crate::grafx::text::Text;
+use crate::grafx::text::atlas::TextRenderMode;
+
+thread_local!{
+    static TEXT3D_SHADER:Shader = unsafe{ Shader::text3d() };
+}
+
+// Text3D shares the fragment shader of Text, only the vertices are placed differently
+fn with_text3d_shader<R>(f:impl FnOnce(&Shader)->R)->R{
+    TEXT3D_SHADER.with(f)
+}
+
+// A Text placed in the world instead of on the screen: its quads lie on the plane of its transform, one
+// pixel of the font size to one world unit unless the transform scales it. A billboarded text always
+// turns its front to the camera, for name tags floating over objects.
+pub struct Text3D{ text:Box<Text>, transform:Box<Transformation3D>, billboard:bool }
+
+#[allow(dead_code)]
+impl Text3D{
+    pub fn new(text:&str)->Self{
+        let mut init = Box::new(Text::new(text));
+        // distance fields stay sharp at every distance from the camera
+        init.set_render_mode(TextRenderMode::DistanceField);
+        Text3D{ text:init, transform:Box::new(Transformation3D::new()), billboard:false }
+    }
+
+    // The text itself, for its string, font, colors and style; its 2D transform is not used
+    pub fn get_label(&mut self)->&mut Text{ &mut self.text }
+    pub fn get_text(&self)->&str{ self.text.get_text() }
+
+    pub fn set_text(&mut self, data:&str){
+        self.text.set_text(data);
+    }
+
+    // Makes the text face the camera; its rotation then turns it relative to the screen
+    pub fn set_billboard(&mut self, billboard:bool){
+        self.billboard = billboard;
+    }
+
+    pub fn is_billboard(&self)->bool{ self.billboard }
+
+    pub fn get_transform(&mut self)->&mut Transformation3D{
+        &mut self.transform
+    }
+
+    // The model matrix of a billboard. It is rotated by the inverse of the camera's rotation, which is the
+    // transpose of the view matrix's upper 3x3, so its axes line up with the screen's.
+    fn get_billboard_matrix(&self, camera:&Camera)->Matrix4{
+        let view = camera.get_view();
+        let mut facing = Matrix4::identity();
+        for (col, values) in view.iter().take(3).enumerate(){
+            for (row, value) in values.iter().take(3).enumerate(){
+                facing.setValue(row, col, *value);
+            }
+        }
+        let rotation = &Matrix4::RotationMatrix(self.transform.get_rotation()) * &Matrix4::ScaleMatrix(self.transform.get_scale());
+        &Matrix4::TranslateMatrix(self.transform.get_position()) * &(&facing * &rotation)
+    }
+
+    // Glyphs are depth tested against what was drawn before them but do not write depth, as their edges
+    // are blended. Like other transparent things, texts have to be drawn after all the opaque geometry,
+    // and from the farthest to the nearest when they overlap each other.
+    pub fn draw(&self, camera:&Camera){
+        unsafe{
+            with_text3d_shader(|shader| {
+                shader.bind();
+                if self.billboard {
+                    shader.set_uniform_matrix4("model", self.get_billboard_matrix(camera).getData());
+                }else{
+                    shader.set_uniform_matrix4("model", self.transform.getTransformMatrix());
+                }
+                shader.set_uniform_matrix4("view", camera.get_view());
+                shader.set_uniform_matrix4("projection", camera.get_projection());
+                self.text.draw_layers(shader);
+            });
+        }
+    }
+}
+
+impl Disposable for Text3D{
+    fn dispose(&mut self) {
+        self.text.dispose();
+    }
+}
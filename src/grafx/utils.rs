@@ -1,7 +1,9 @@
 use freetype::freetype_sys::FT_Vector;
 
+#[derive(Clone, Copy)]
 pub struct Bearing(pub i32, pub i32);
 
+#[derive(Clone, Copy)]
 pub struct Size{ width:i32, height:i32}
 impl Size{
     pub fn new(width:i32, height:i32)->Self{
@@ -12,19 +14,27 @@ impl Size{
     pub fn get_height(&self)->i32{ self.height }
 }
 
+#[derive(Clone)]
 pub struct Character{
-    texture_id : u32,
+    page : usize,       // Index of the glyph atlas page holding the bitmap
+    uv: [f32; 4],       // Left, top, right and bottom texture coordinates inside the page
     size: Size,      // Size of glyph
     bearing: Bearing,   // Offset from baseline to left/top of glyph
     advance: FT_Vector 
 }
 
 impl Character{
-    pub fn new(texture_id:u32, width:i32, height:i32, x:i32, y:i32, advance:FT_Vector)->Self{
-        Character{ texture_id, size:Size::new(width, height), bearing:Bearing(x, y), advance }
+    pub fn new(page:usize, uv:[f32; 4], size:Size, bearing:Bearing, advance:FT_Vector)->Self{
+        Character{ page, uv, size, bearing, advance }
     }
 
-    pub fn get_texture(&self)->u32{ self.texture_id }
+    // A glyph that takes no space and draws nothing
+    pub fn empty()->Self{
+        Character::new(0, [0.0; 4], Size::new(0, 0), Bearing(0, 0), FT_Vector::default())
+    }
+
+    pub fn get_page(&self)->usize{ self.page }
+    pub fn get_uv(&self)->&[f32; 4]{ &self.uv }
     pub fn get_size(&self)->&Size{ &self.size }
     pub fn get_bearing(&self)->&Bearing{ &self.bearing }
     pub fn get_advance(&self)->&FT_Vector{ &self.advance }
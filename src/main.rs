@@ -2,9 +2,16 @@ mod grafx;
 
 use crate::grafx::text::Collidable;
 use crate::grafx::text::Text;
-use crate::grafx::physics::Viewport;
+use crate::grafx::text::TextBatch;
+use crate::grafx::text::TextInput;
+use crate::grafx::text::TextMesh;
+use crate::grafx::text::Text3D;
+use crate::grafx::text::{ HorizontalAlign, VerticalAlign, TextStyle, TextFill };
+use crate::grafx::physics::{ Camera, Color, Vector2, Viewport };
+use crate::grafx::materials::{ BasicMaterial, Material };
 use crate::grafx::Disposable;
 use grafx::{ WindowHandler, WindowDetails};
+use glutin::event::{ ElementState, MouseButton, WindowEvent };
 
 struct TextObject{ text: Box<Text>,  delta_x:f32, delta_y:f32, rotation:f32 }
 impl TextObject{
@@ -12,6 +19,11 @@ impl TextObject{
         let mut init = Box::new(Text::new(text));
         init.get_transform().setPosition(x, y);
         init.set_font_size(size);
+        // outlined so the labels stay readable over each other
+        let mut style = TextStyle::new();
+        style.set_outline(1.5, Color::Black());
+        style.set_shadow(2.0, -2.0, 1.0, Color::new(0.0, 0.0, 0.0, 0.5));
+        init.set_style(style);
         TextObject{ text:init, delta_x:120.0, delta_y:100.0, rotation:45.0 }
     }
 
@@ -50,8 +62,8 @@ impl TextObject{
         }
     }
 
-    pub fn draw(&self, port:&Viewport){
-        self.text.draw(port);
+    pub fn draw(&self, batch:&mut TextBatch){
+        batch.add(&self.text);
     }
 }
 
@@ -62,7 +74,8 @@ impl Disposable for TextObject{
 }
 
 #[allow(non_snake_case)]
-struct Test{ port:Box<Viewport>, texts:Vec<Box<TextObject>>, copy:Box<Text>, fps:Box<Text> }
+struct Test{ port:Box<Viewport>, batch:Box<TextBatch>, texts:Vec<Box<TextObject>>, copy:Box<Text>, fps:Box<Text>, input:Box<TextInput>, cursor:(f32, f32),
+    title:Box<TextMesh>, tag:Box<Text3D>, material:Box<BasicMaterial>, camera:Box<Camera> }
 
 impl Test{
     unsafe fn new()->Self{
@@ -90,20 +103,53 @@ impl Test{
         texts.push(Box::new(TextObject::new("Phyton", 15, 100.0, 240.0)));
         texts.push(Box::new(TextObject::new("C++", 15, 100.0, 240.0)));
         texts.push(Box::new(TextObject::new("Programming", 15, 100.0, 240.0)));
+        texts.last_mut().unwrap().text.set_markup("[color=#ffd040]Pro[/color]gramming [u]in[/u] [size=20][b]Rust[/b][/size]");
+        texts.push(Box::new(TextObject::new("Zoë Ångström", 18, 240.0, 160.0)));
 
         let mut copy = Text::new("Bsoft Limited");
-        copy.set_color(0.2, 0.2, 0.2, 1.0);
+        copy.set_fill(TextFill::LinearGradient{ start:(0.0, 0.0), end:(1.0, 0.0), stops:vec![
+            (0.0, Color::new(0.2, 0.2, 0.2, 1.0)), (1.0, Color::new(0.2, 0.4, 0.8, 1.0))
+        ] });
         let width = copy.get_width();
         let height = copy.get_height();
         copy.get_transform().setPosition(width / 2.0 + 5.0 , height / 2.0 + 5.0);
 
         let mut fps = Text::new("FTP: 00");
         fps.set_font_size(16);
-        let fps_width = fps.get_width();
-        let fps_height = fps.get_height();
-        fps.get_transform().setPosition( 800.0 - fps_width - 10.0, 480.0 - fps_height - 10.0);
+        fps.set_alignment(HorizontalAlign::Right, VerticalAlign::Top);
+        fps.get_transform().setPosition( 800.0 - 10.0, 480.0 - 10.0);
         fps.set_color(0.0, 0.0, 0.8, 1.0);
-        Test{ port:Box::new(Viewport::new(800.0, 480.0)), texts, copy:Box::new(copy), fps:Box::new(fps) }
+
+        let mut input = TextInput::new("Type here");
+        input.get_label().set_font_size(18);
+        input.get_label().set_color(0.1, 0.1, 0.1, 1.0);
+        input.get_label().set_alignment(HorizontalAlign::Left, VerticalAlign::Top);
+        input.get_label().get_transform().setPosition(10.0, 480.0 - 10.0);
+        input.set_focused(true);
+        input.select_all();
+
+        // a 3D title spinning behind the labels, 64 pixels of font size to a world unit
+        let mut title = TextMesh::new("Rust", 16.0);
+        title.set_font_size(64);
+        title.get_transform().setScale(1.0 / 64.0, 1.0 / 64.0, 1.0 / 64.0);
+        title.get_transform().update();
+        // a name tag floating over it, always facing the camera
+        let mut tag = Text3D::new("Ferris");
+        tag.get_label().set_font_size(24);
+        tag.get_label().set_color(0.1, 0.1, 0.1, 1.0);
+        tag.set_billboard(true);
+        tag.get_transform().setPosition(0.0, 1.0, 0.0);
+        tag.get_transform().setScale(1.0 / 64.0, 1.0 / 64.0, 1.0 / 64.0);
+        let mut material = BasicMaterial::new();
+        material.set_diffuse_color(Color::new(0.85, 0.4, 0.1, 1.0));
+        let mut camera = Camera::new(45.0, 800.0, 480.0);
+        camera.set_position(0.0, 0.5, 4.0);
+        camera.update();
+        Test{
+            port:Box::new(Viewport::new(800.0, 480.0)), batch:Box::new(TextBatch::new()), texts, copy:Box::new(copy), fps:Box::new(fps),
+            input:Box::new(input), cursor:(0.0, 0.0),
+            title:Box::new(title), tag:Box::new(tag), material:Box::new(material), camera:Box::new(camera)
+        }
     }
 
     
@@ -115,14 +161,38 @@ impl WindowHandler for Test {
             text.update(delta, self.port.as_ref());
         }
         self.fps.set_text(format!("FPS: {}", (1.0 / delta) as i32).as_ref());
+        self.input.update(delta);
+        self.title.get_transform().rotate(0.0, 30.0 * delta, 0.0);
+        self.title.get_transform().update();
+    }
+
+    fn event(&mut self, event:&WindowEvent){
+        match event {
+            // the window counts from the top, the viewport from the bottom
+            WindowEvent::CursorMoved{ position, .. } => self.cursor = (position.x as f32, self.port.get_height() - position.y as f32),
+            WindowEvent::MouseInput{ state:ElementState::Pressed, button:MouseButton::Left, .. } => {
+                let focused = self.input.place_caret(&Vector2::new(self.cursor.0, self.cursor.1), false);
+                self.input.set_focused(focused);
+            },
+            _ => { self.input.handle_event(event); },
+        }
+    }
+
+    fn get_ime_position(&self)->Option<(f32, f32)>{
+        if self.input.is_focused() { Some(self.input.get_ime_position()) } else { None }
     }
 
-    unsafe fn render(&self) {
+    unsafe fn render(&mut self) {
+        self.title.draw(self.material.as_ref(), self.camera.as_ref());
+        self.tag.draw(self.camera.as_ref());
+        self.batch.begin();
         for text in &self.texts{
-            text.draw(self.port.as_ref());
+            text.draw(self.batch.as_mut());
         }
-        self.copy.draw(self.port.as_ref());
-        self.fps.draw(self.port.as_ref());
+        self.batch.add(self.copy.as_ref());
+        self.batch.add(self.fps.as_ref());
+        self.batch.end(self.port.as_ref());
+        self.input.draw(self.port.as_ref());
     }
 
     fn resize(&mut self, width: i32, height: i32){
@@ -138,6 +208,11 @@ impl Disposable for Test {
         }
         self.copy.dispose();
         self.fps.dispose();
+        self.input.dispose();
+        self.title.dispose();
+        self.tag.dispose();
+        self.material.dispose();
+        self.batch.dispose();
     }
 }
 